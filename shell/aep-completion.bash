@@ -0,0 +1,94 @@
+# 
+# SPDX-License-Identifier: MIT
+# 
+# Copyright (c) 2025 Andrew Ellis Page
+# 
+# Permission is hereby granted, free of charge, to any person obtaining a copy
+# of this software and associated documentation files (the "Software"), to deal
+# in the Software without restriction, including without limitation the rights
+# to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+# copies of the Software, and to permit persons to whom the Software is
+# furnished to do so, subject to the following conditions:
+# 
+# The above copyright notice and this permission notice shall be included in all
+# copies or substantial portions of the Software.
+# 
+# THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+# IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+# FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+# AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+# LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+# OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+# SOFTWARE.
+# 
+#
+# Reads version 1 of the aep completion protocol, see `write_protocol` in
+# src/completion.rs, into COMPREPLY.
+#
+# _aep_complete COMMAND [ARGS...] runs
+#     COMMAND [ARGS...] PREVIOUS-WORD CURRENT-WORD
+# which must write the protocol, e.g. by calling
+# YamlDescender::write_completions_after with descriptions on.  Use it from
+# a completion function:
+#
+#     _mytool() { _aep_complete mytool --complete; }
+#     complete -F _mytool mytool
+#
+# The words are passed to COMMAND with the shell's quoting removed, and the
+# candidates are quoted for the shell, so that a path escape such as
+# `registers.v1\.2` still reaches the program when the command line runs.
+#
+
+# $1 with one level of shell quoting removed, in REPLY, without evaluating it
+_aep_dequote() {
+    local word=$1 out= c quote= i
+    for (( i = 0; i < ${#word}; i++ )); do
+        c=${word:i:1}
+        if [[ $quote == "'" ]]; then
+            if [[ $c == "'" ]]; then quote=; else out+=$c; fi
+        elif [[ $c == '\' ]]; then
+            (( i++ ))
+            # inside double quotes only these are escaped
+            [[ $quote == '"' && ${word:i:1} != [\"\\\$\`] ]] && out+='\'
+            out+=${word:i:1}
+        elif [[ -n $quote && $c == "$quote" ]]; then
+            quote=
+        elif [[ -z $quote && ( $c == "'" || $c == '"' ) ]]; then
+            quote=$c
+        else
+            out+=$c
+        fi
+    done
+    REPLY=$out
+}
+
+_aep_complete() {
+    local REPLY cur prev=
+    _aep_dequote "${COMP_WORDS[COMP_CWORD]}"
+    cur=$REPLY
+    if (( COMP_CWORD > 0 )); then
+        _aep_dequote "${COMP_WORDS[COMP_CWORD-1]}"
+        prev=$REPLY
+    fi
+    local line text rest flags header nospace=
+    COMPREPLY=()
+    {
+        IFS= read -r header || return 1
+        [[ $header == "aep-completion 1" ]] || return 1
+        while IFS= read -r line; do
+            text=${line%%$'\t'*}
+            rest=${line#*$'\t'}
+            flags=${rest#*$'\t'}
+            printf -v text '%b' "$text"
+            printf -v text '%q' "$text"
+            COMPREPLY+=("$text")
+            [[ ,$flags, == *,nospace,* ]] && nospace=1
+        done
+    } < <("$@" "$prev" "$cur")
+    [[ -n $nospace ]] && compopt -o nospace 2>/dev/null
+    # completions holding ':' would otherwise be split by COMP_WORDBREAKS
+    if declare -F __ltrim_colon_completions >/dev/null; then
+        __ltrim_colon_completions "$cur"
+    fi
+    return 0
+}
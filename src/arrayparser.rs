@@ -21,40 +21,90 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 // 
-use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+use yaml_rust::Yaml;
+use crate::error::YamlPathError;
+use crate::path::{escape_pointer_token, tokenize_pointer, DottedSyntax, PartialPath, Segment, YamlPath};
+use crate::rust_common::sep;
 
-/// A trait for parsing an array-like string structure using regular expressions.
+/// A trait describing a path dialect, principally how arrays are indexed.
 ///
 /// The `ArrayParser` trait provides a blueprint for implementing types that
-/// can handle parsing or extracting data from array-like string structures
-/// using regular expressions.
+/// turn path strings into [`YamlPath`] values and format them back.
 ///
 /// # Required Methods
 ///
-/// - `get_re`: Returns a `Regex` instance to be used for parsing.
+/// - `parse_path`: Tokenizes a complete path, failing on a trailing
+///   separator or an unterminated index.
+/// - `parse_partial`: Tokenizes a path that may still be being typed.
+/// - `apply_index`: Formats a single array index, `[0]` or `@0`.
+/// - `apply_selector`: Formats a negative index, a slice or a filter,
+///   `[-1]`, `@1:3` or `[?name==GPIO4]`.
 ///
-/// # Methods
+/// # Provided Methods
 ///
-/// ## `fn get_re() -> Regex;`
-/// - **Description:**
-///     This method returns a `Regex` object that can be used to handle
-///     parsing array-like patterns in strings.
-/// - **Return:**
-///     - A `Regex` instance that represents the pattern for parsing array data.
-/// - **Implementation:**
-///     This has to be implemented by any type that implements the `ArrayParser` trait.
-/// 
-///
-/// # Notes
-/// - Users of this trait must ensure to handle the `regex` crate dependency in their project.
-/// - Proper error handling is advised when dealing with complex regular expressions.
+/// - `format_key`: Formats a single hash key, escaping any character the
+///   dialect would otherwise read as structure.
+/// - `format_path`: Formats a whole [`YamlPath`] in this dialect.
+/// - `separator`: What follows a node's path before one of its members,
+///   `.` after a hash in the dotted dialects.
+/// - `parse_reference`: Parses the value of a `parent:` key.  References
+///   starting with `/` are JSON Pointers whatever the dialect.
 pub trait ArrayParser : Send + Sync {
-    fn get_re(&self) -> Regex;
+    fn parse_path(&self, path: &str) -> Result<YamlPath, YamlPathError>;
+
+    fn parse_partial(&self, path: &str) -> Result<PartialPath, YamlPathError>;
+
     fn apply_index(&self, index: usize) -> String;
 
-    fn array_ending(&self, s: &str) -> bool ;
+    /// format a negative index, slice or filter, `selector` is `-1`, `1:3`
+    /// or `?field==value`
+    fn apply_selector(&self, selector: &str) -> String;
+
+    fn format_key(&self, key: &str) -> String {
+        key.to_string()
+    }
+
+    fn separator(&self, node: &Yaml) -> &'static str {
+        sep(node, false)
+    }
+
+    fn parse_reference(&self, reference: &str) -> Result<YamlPath, YamlPathError> {
+        if reference.starts_with('/') {
+            tokenize_pointer(reference, false).map(|p| p.path)
+        } else {
+            self.parse_path(reference)
+        }
+    }
+
+    fn format_path(&self, path: &YamlPath) -> String {
+        let mut s = String::new();
+        for segment in path.segments() {
+            match segment {
+                Segment::Key(k) => {
+                    if !s.is_empty() {
+                        s.push('.');
+                    }
+                    s += &self.format_key(k);
+                }
+                Segment::Index(i) => s += &self.apply_index(*i),
+                Segment::NegativeIndex(_) | Segment::Slice { .. } | Segment::Filter(_) => {
+                    s += &self.apply_selector(&segment.selector().unwrap_or_default())
+                }
+                Segment::Wildcard | Segment::RecursiveDescent => {
+                    if !s.is_empty() {
+                        s.push('.');
+                    }
+                    s += if *segment == Segment::Wildcard { "*" } else { "**" };
+                }
+            }
+        }
+        s
+    }
 }
 
+#[derive(Default)]
 pub struct BashArrayParser {
 }
 
@@ -64,6 +114,7 @@ impl BashArrayParser {
     }
 }
 
+#[derive(Default)]
 pub struct ZshArrayParser {
 }
 
@@ -73,29 +124,230 @@ impl ZshArrayParser {
     }
 }
 
+const BASH_SYNTAX: DottedSyntax = DottedSyntax { open: '[', close: Some(']') };
+const ZSH_SYNTAX: DottedSyntax = DottedSyntax { open: '@', close: None };
+
 impl ArrayParser for BashArrayParser {
-    fn get_re(&self) -> Regex {
-        Regex::new(r"([^.\[\]\\]+)(\.)?|(?:\[(\d+)]?)?").unwrap()
+    fn parse_path(&self, path: &str) -> Result<YamlPath, YamlPathError> {
+        BASH_SYNTAX.tokenize(path, false).map(|p| p.path)
+    }
+
+    fn parse_partial(&self, path: &str) -> Result<PartialPath, YamlPathError> {
+        BASH_SYNTAX.tokenize(path, true)
     }
 
     fn apply_index(&self, index: usize) -> String {
-        format!("[{}]", index)
+        BASH_SYNTAX.format_index(index)
+    }
+
+    fn apply_selector(&self, selector: &str) -> String {
+        BASH_SYNTAX.format_selector(selector)
     }
 
-    fn array_ending(&self, s: &str) -> bool {
-        s.ends_with('[')
+    fn format_key(&self, key: &str) -> String {
+        BASH_SYNTAX.escape_key(key)
     }
 }
 
 impl ArrayParser for ZshArrayParser {
-    fn get_re(&self) -> Regex {
-        Regex::new(r"([^.\[\]\\@]+)(\.)?|(?:@(\d+))?").unwrap()
+    fn parse_path(&self, path: &str) -> Result<YamlPath, YamlPathError> {
+        ZSH_SYNTAX.tokenize(path, false).map(|p| p.path)
+    }
+
+    fn parse_partial(&self, path: &str) -> Result<PartialPath, YamlPathError> {
+        ZSH_SYNTAX.tokenize(path, true)
+    }
+
+    fn apply_index(&self, index: usize) -> String {
+        ZSH_SYNTAX.format_index(index)
+    }
+
+    fn apply_selector(&self, selector: &str) -> String {
+        ZSH_SYNTAX.format_selector(selector)
+    }
+
+    fn format_key(&self, key: &str) -> String {
+        ZSH_SYNTAX.escape_key(key)
+    }
+}
+
+///
+/// Paths for fish, arrays indexed as `@index` like zsh.  Fish never expands
+/// `@`, where older versions glob the `?` of a bracketed filter.  Keys also
+/// escape the characters fish expands, `*`, `?`, `$`, `(`, `)`, `{` and
+/// `}`.  A typed wildcard or filter, `pins@*.level`, must still be quoted.
+///
+#[derive(Default)]
+pub struct FishArrayParser {
+}
+
+impl FishArrayParser {
+    pub fn new() -> Self {
+        Self{}
+    }
+}
+
+const FISH_SPECIAL: [char; 7] = ['*', '?', '$', '(', ')', '{', '}'];
+
+impl ArrayParser for FishArrayParser {
+    fn parse_path(&self, path: &str) -> Result<YamlPath, YamlPathError> {
+        ZSH_SYNTAX.tokenize(path, false).map(|p| p.path)
+    }
+
+    fn parse_partial(&self, path: &str) -> Result<PartialPath, YamlPathError> {
+        ZSH_SYNTAX.tokenize(path, true)
+    }
+
+    fn apply_index(&self, index: usize) -> String {
+        ZSH_SYNTAX.format_index(index)
+    }
+
+    fn apply_selector(&self, selector: &str) -> String {
+        ZSH_SYNTAX.format_selector(selector)
+    }
+
+    fn format_key(&self, key: &str) -> String {
+        ZSH_SYNTAX.escape_key_with(key, |c| FISH_SPECIAL.contains(&c))
+    }
+}
+
+///
+/// RFC 6901 JSON Pointers, `/GPIO/pins/0/set`
+///
+#[derive(Default)]
+pub struct JsonPointerParser {
+}
+
+impl JsonPointerParser {
+    pub fn new() -> Self {
+        Self{}
+    }
+}
+
+impl ArrayParser for JsonPointerParser {
+    fn parse_path(&self, path: &str) -> Result<YamlPath, YamlPathError> {
+        tokenize_pointer(path, false).map(|p| p.path)
+    }
+
+    fn parse_partial(&self, path: &str) -> Result<PartialPath, YamlPathError> {
+        tokenize_pointer(path, true)
     }
+
     fn apply_index(&self, index: usize) -> String {
-        format!("@{}", index)
+        format!("/{}", index)
+    }
+
+    fn apply_selector(&self, selector: &str) -> String {
+        format!("/{}", escape_pointer_token(selector))
+    }
+
+    fn format_key(&self, key: &str) -> String {
+        format!("/{}", escape_pointer_token(key))
+    }
+
+    fn format_path(&self, path: &YamlPath) -> String {
+        let mut s = String::new();
+        for segment in path.segments() {
+            s += &match segment {
+                Segment::Key(k) => self.format_key(k),
+                Segment::Index(i) => self.apply_index(*i),
+                Segment::Wildcard => String::from("/*"),
+                Segment::RecursiveDescent => String::from("/**"),
+                _ => self.apply_selector(&segment.selector().unwrap_or_default()),
+            };
+        }
+        s
+    }
+
+    /// every member starts with its own '/'
+    fn separator(&self, _node: &Yaml) -> &'static str {
+        ""
+    }
+
+    /// config files are shared between dialects, so a reference that is
+    /// not a pointer is read as a bash path
+    fn parse_reference(&self, reference: &str) -> Result<YamlPath, YamlPathError> {
+        if reference.is_empty() || reference.starts_with('/') {
+            self.parse_path(reference)
+        } else {
+            BASH_SYNTAX.tokenize(reference, false).map(|p| p.path)
+        }
     }
+}
+
+///
+/// The path dialects understood by [`YamlDescender`](crate::yaml_descender::YamlDescender)
+///
+/// - `Bash`: arrays are indexed with `[index]`
+/// - `Zsh`: arrays are indexed as `@index`
+/// - `JsonPointer`: RFC 6901 pointers, `/key/index`
+/// - `Fish`: arrays are indexed as `@index`, completions are written as
+///   fish reads them
+///
+/// `true` and `false` convert to `Bash` and `Zsh` for the older
+/// `bash_or_zsh` constructor argument.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Dialect {
+    #[default]
+    Bash,
+    Zsh,
+    JsonPointer,
+    Fish,
+}
+
+impl Dialect {
+    pub fn array_parser(self) -> Box<dyn ArrayParser> {
+        match self {
+            Dialect::Bash => Box::new(BashArrayParser::new()),
+            Dialect::Zsh => Box::new(ZshArrayParser::new()),
+            Dialect::JsonPointer => Box::new(JsonPointerParser::new()),
+            Dialect::Fish => Box::new(FishArrayParser::new()),
+        }
+    }
+
+    /// parse `path` in this dialect
+    pub fn parse(self, path: &str) -> Result<YamlPath, YamlPathError> {
+        self.array_parser().parse_path(path)
+    }
+
+    /// format `path` in this dialect
+    pub fn format(self, path: &YamlPath) -> String {
+        self.array_parser().format_path(path)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Bash => "bash",
+            Dialect::Zsh => "zsh",
+            Dialect::JsonPointer => "json-pointer",
+            Dialect::Fish => "fish",
+        }
+    }
+}
+
+impl From<bool> for Dialect {
+    fn from(bash_or_zsh: bool) -> Self {
+        if bash_or_zsh { Dialect::Bash } else { Dialect::Zsh }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = YamlPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Dialect::Bash),
+            "zsh" => Ok(Dialect::Zsh),
+            "json-pointer" | "json" => Ok(Dialect::JsonPointer),
+            "fish" => Ok(Dialect::Fish),
+            _ => Err(YamlPathError::ParseError { path: s.to_string(), segment: String::new(), message: String::from("unknown dialect") }),
+        }
+    }
+}
 
-    fn array_ending(&self, s: &str) -> bool {
-        s.ends_with('@')
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,223 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::io::Write;
+use yaml_rust::Yaml;
+
+/// what a completion leads to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Hash,
+    Array,
+    /// a scalar, or a value that is not a node such as a file name
+    Leaf,
+}
+
+impl NodeKind {
+    pub fn of(yaml: &Yaml) -> NodeKind {
+        match yaml {
+            Yaml::Hash(_) => NodeKind::Hash,
+            Yaml::Array(_) => NodeKind::Array,
+            _ => NodeKind::Leaf,
+        }
+    }
+}
+
+///
+/// One candidate offered for the word being completed
+///
+/// - `text`: what replaces the word, `GPIO.pins[0]`
+/// - `display`: the short form to list, `[0]`
+/// - `description`: the node's, possibly inherited, `description`
+/// - `kind`: whether `text` names a hash, an array or a leaf
+/// - `terminal`: completion cannot continue below `text`, it is a leaf or
+///   holds a terminal field
+/// - `no_space`: the shell should not add a space after `text`, more of
+///   the path is expected
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub text: String,
+    pub display: String,
+    pub description: Option<String>,
+    pub kind: NodeKind,
+    pub terminal: bool,
+    pub no_space: bool,
+}
+
+impl Completion {
+    /// a candidate naming `node`
+    pub(crate) fn node(text: String, display: String, description: Option<String>, node: &Yaml, terminal: bool) -> Completion {
+        let kind = NodeKind::of(node);
+        let terminal = terminal || kind == NodeKind::Leaf;
+        Completion { text, display, description, kind, terminal, no_space: !terminal }
+    }
+
+    /// a candidate that is not a node, a value or a file name, directories
+    /// ending in `/` expect more
+    pub(crate) fn word(text: String) -> Completion {
+        let more = text.ends_with('/');
+        Completion { display: text.clone(), text, description: None, kind: NodeKind::Leaf, terminal: !more, no_space: more }
+    }
+}
+
+/// the first line of protocol output, naming its version
+pub const PROTOCOL_HEADER: &str = "aep-completion 1";
+
+/// the bash function reading the protocol, see `shell/aep-completion.bash`
+pub const BASH_FUNCTION: &str = include_str!("../shell/aep-completion.bash");
+
+/// the zsh function reading the protocol, see `shell/aep-completion.zsh`
+pub const ZSH_FUNCTION: &str = include_str!("../shell/aep-completion.zsh");
+
+///
+/// `s` with backslash, tab, carriage return and newline written as `\\`,
+/// `\t`, `\r` and `\n`, so that it fits in one protocol field
+///
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// reverse [`escape`], an unknown escape is kept as it is
+pub fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => { unescaped.push('\\'); unescaped.push(c); }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+///
+/// Write each candidate's `text` on a line of its own, as the dialect
+/// formatted it.  Only the protocol is safe for text holding a newline.
+///
+pub fn write_plain(writer: &mut dyn Write, completions: &[Completion]) -> std::io::Result<()> {
+    for c in completions {
+        writer.write_fmt(format_args!("{}\n", c.text))?;
+    }
+    Ok(())
+}
+
+///
+/// Write `completions` in version 1 of the completion protocol.
+///
+/// The first line is [`PROTOCOL_HEADER`], then there is one line per
+/// candidate holding three tab separated fields:
+///
+/// ```text
+/// aep-completion 1
+/// GPIO.pins<TAB>the pins<TAB>nospace
+/// GPIO.base<TAB><TAB>
+/// ```
+///
+/// - the text to insert
+/// - its description, empty when it has none
+/// - flags separated by commas, `nospace` when the shell should not add a
+///   space after the text
+///
+/// Both text fields are [`escape`]d, so a line is always one candidate.
+/// A reader should ignore flags it does not know and refuse other
+/// versions.
+///
+pub fn write_protocol(writer: &mut dyn Write, completions: &[Completion]) -> std::io::Result<()> {
+    writer.write_fmt(format_args!("{}\n", PROTOCOL_HEADER))?;
+    for c in completions {
+        let description = c.description.as_deref().map(escape).unwrap_or_default();
+        let flags = if c.no_space { "nospace" } else { "" };
+        writer.write_fmt(format_args!("{}\t{}\t{}\n", escape(&c.text), description, flags))?;
+    }
+    Ok(())
+}
+
+///
+/// Write `completions` as fish reads the output of a completion command,
+/// `candidate<TAB>description` or just the candidate.  Fish shows a
+/// description on one line, so newlines and tabs become spaces.
+///
+pub fn write_fish(writer: &mut dyn Write, completions: &[Completion]) -> std::io::Result<()> {
+    let one_line = |s: &str| s.replace(['\n', '\r', '\t'], " ");
+    for c in completions {
+        match &c.description {
+            Some(d) => writer.write_fmt(format_args!("{}\t{}\n", one_line(&c.text), one_line(d)))?,
+            None => writer.write_fmt(format_args!("{}\n", one_line(&c.text)))?,
+        }
+    }
+    Ok(())
+}
+
+///
+/// A fish script completing `tool` from `command`, a program and its
+/// arguments, which is run as `command... PREVIOUS-WORD CURRENT-WORD` and
+/// should write [`write_fish`] output, for example from a
+/// [`Dialect::Fish`](crate::arrayparser::Dialect::Fish) descender's
+/// `write_completions_after`.  `tool` and each word of `command` are
+/// quoted for fish.
+///
+/// # Example
+/// ```rust
+/// use aep_rust_common::completion::fish_script;
+/// let script = fish_script("regtool", &["regtool", "--complete"]);
+/// assert!(script.contains("'regtool' '--complete' $tokens[-1] (commandline -ct)"));
+/// assert!(script.contains("complete -c 'regtool' -f -a '(__regtool_complete)'"));
+/// ```
+///
+pub fn fish_script(tool: &str, command: &[&str]) -> String {
+    let function: String = tool.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let command: Vec<String> = command.iter().map(|word| fish_quote(word)).collect();
+    let command = command.join(" ");
+    let tool = fish_quote(tool);
+    format!(r#"# fish completion for {function}
+function __{function}_complete
+    set -l tokens (commandline -opc)
+    {command} $tokens[-1] (commandline -ct)
+end
+complete -c {tool} -f -a '(__{function}_complete)'
+"#)
+}
+
+/// `s` as one fish word, single quoted, a quote or backslash escaped
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
@@ -0,0 +1,169 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::fmt;
+
+///
+/// Errors produced while parsing or descending a yaml tree by path.
+///
+/// Every variant carries the `path` being resolved and the `segment` of that
+/// path where resolution failed so callers can tell a missing key from a
+/// type mismatch without inspecting the message text.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum YamlPathError {
+    /// a hash does not contain the requested key
+    KeyNotFound { path: String, segment: String },
+    /// an index is past the end of an array
+    IndexOutOfBounds { path: String, segment: String },
+    /// a key was applied to something other than a hash
+    NotAHash { path: String, segment: String },
+    /// an index was applied to something other than an array
+    NotAnArray { path: String, segment: String },
+    /// the value was found but is not of the expected type,
+    /// `expected` reads as "a string", "an integer", etc.
+    TypeMismatch { path: String, segment: String, expected: &'static str },
+    /// the path itself could not be parsed, `position` is the byte offset
+    /// of the offending `segment`
+    InvalidPath { path: String, segment: String, position: usize },
+    /// a `parent` chain refers back to itself, `path` names the chain,
+    /// `a -> b -> a`, and `segment` the reference closing it
+    ParentCycle { path: String, segment: String },
+    /// a `parent` chain is longer than `limit`, `path` names the chain and
+    /// `segment` the reference that would exceed it
+    ParentDepthExceeded { path: String, segment: String, limit: usize },
+    /// the yaml source, or `path` when it names something such as a
+    /// dialect, could not be read
+    ParseError { path: String, segment: String, message: String },
+    /// `segment` is not one of the keys allowed in `path`
+    UnknownKey { path: String, segment: String },
+    /// `completion-metadata` failed validation, `problems` holds every
+    /// problem found
+    InvalidMetadata { path: String, segment: String, problems: Vec<YamlPathError> },
+}
+
+impl YamlPathError {
+    pub fn path(&self) -> &str {
+        match self {
+            YamlPathError::KeyNotFound { path, .. }
+            | YamlPathError::IndexOutOfBounds { path, .. }
+            | YamlPathError::NotAHash { path, .. }
+            | YamlPathError::NotAnArray { path, .. }
+            | YamlPathError::TypeMismatch { path, .. }
+            | YamlPathError::InvalidPath { path, .. }
+            | YamlPathError::ParentCycle { path, .. }
+            | YamlPathError::ParentDepthExceeded { path, .. }
+            | YamlPathError::ParseError { path, .. }
+            | YamlPathError::UnknownKey { path, .. }
+            | YamlPathError::InvalidMetadata { path, .. } => path,
+        }
+    }
+
+    pub fn segment(&self) -> &str {
+        match self {
+            YamlPathError::KeyNotFound { segment, .. }
+            | YamlPathError::IndexOutOfBounds { segment, .. }
+            | YamlPathError::NotAHash { segment, .. }
+            | YamlPathError::NotAnArray { segment, .. }
+            | YamlPathError::TypeMismatch { segment, .. }
+            | YamlPathError::InvalidPath { segment, .. }
+            | YamlPathError::ParentCycle { segment, .. }
+            | YamlPathError::ParentDepthExceeded { segment, .. }
+            | YamlPathError::ParseError { segment, .. }
+            | YamlPathError::UnknownKey { segment, .. }
+            | YamlPathError::InvalidMetadata { segment, .. } => segment,
+        }
+    }
+
+    /// the same error reported against `path`
+    pub(crate) fn with_path(mut self, path: &str) -> Self {
+        match &mut self {
+            YamlPathError::KeyNotFound { path: p, .. }
+            | YamlPathError::IndexOutOfBounds { path: p, .. }
+            | YamlPathError::NotAHash { path: p, .. }
+            | YamlPathError::NotAnArray { path: p, .. }
+            | YamlPathError::TypeMismatch { path: p, .. }
+            | YamlPathError::InvalidPath { path: p, .. }
+            | YamlPathError::ParentCycle { path: p, .. }
+            | YamlPathError::ParentDepthExceeded { path: p, .. }
+            | YamlPathError::ParseError { path: p, .. }
+            | YamlPathError::UnknownKey { path: p, .. }
+            | YamlPathError::InvalidMetadata { path: p, .. } => *p = path.to_string(),
+        }
+        self
+    }
+}
+
+/// 'path.segment', or whichever of the two is present
+fn location(path: &str, segment: &str) -> String {
+    match (path.is_empty(), segment.is_empty()) {
+        (true, true) => String::from("document"),
+        (true, false) => segment.to_string(),
+        (false, true) => path.to_string(),
+        (false, false) => format!("{}.{}", path, segment),
+    }
+}
+
+impl fmt::Display for YamlPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YamlPathError::KeyNotFound { path, segment } if path.is_empty() => write!(f, "{} not found", segment),
+            YamlPathError::KeyNotFound { path, segment } => write!(f, "{} not found in {}", segment, path),
+            YamlPathError::IndexOutOfBounds { path, segment } => write!(f, "{} is out of bounds in {}", segment, path),
+            YamlPathError::NotAHash { path, segment } if path.is_empty() => write!(f, "{} is not a hash", segment),
+            YamlPathError::NotAHash { path, segment } if segment.is_empty() => write!(f, "{} is not a hash", path),
+            YamlPathError::NotAHash { path, segment } => write!(f, "{} in {} is not a hash", segment, path),
+            YamlPathError::NotAnArray { path, segment } => write!(f, "{} {} is not an array", path, segment),
+            YamlPathError::TypeMismatch { path, segment, expected } => {
+                write!(f, "{} is not {}", location(path, segment), expected)
+            }
+            YamlPathError::InvalidPath { path, segment, position } if segment.is_empty() => {
+                write!(f, "{} is not a valid path: unexpected end at {}", path, position)
+            }
+            YamlPathError::InvalidPath { path, segment, position } => {
+                write!(f, "{} is not a valid path: unexpected '{}' at {}", path, segment, position)
+            }
+            YamlPathError::ParentCycle { path, .. } => write!(f, "parent cycle {}", path),
+            YamlPathError::ParentDepthExceeded { path, limit, .. } => {
+                write!(f, "parent chain {} is longer than {}", path, limit)
+            }
+            YamlPathError::ParseError { path, segment, message } => {
+                write!(f, "failed to parse {}", if path.is_empty() { "yaml" } else { path })?;
+                if !segment.is_empty() {
+                    write!(f, " at {}", segment)?;
+                }
+                write!(f, ": {}", message)
+            }
+            YamlPathError::UnknownKey { path, segment } => write!(f, "{} is not a known key of {}", segment, path),
+            YamlPathError::InvalidMetadata { path, problems, .. } => {
+                write!(f, "invalid {}", path)?;
+                for (i, problem) in problems.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for YamlPathError {}
@@ -0,0 +1,161 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::cell::RefCell;
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
+use crate::arrayparser::BashArrayParser;
+use crate::error::YamlPathError;
+use crate::path::{Segment, YamlPath};
+use crate::yaml_path::{Ancestors, DEFAULT_MAX_PARENT_DEPTH};
+
+///
+/// What [`flatten`] keeps of the inheritance it resolves
+///
+/// - `keep_parent`: leave each node's own `parent` key in place
+/// - `keep_description`: keep `description` keys, inherited ones included
+///
+/// The default strips `parent` and keeps descriptions.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlattenOptions {
+    pub keep_parent: bool,
+    pub keep_description: bool,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions { keep_parent: false, keep_description: true }
+    }
+}
+
+///
+/// Copy `yaml` with every inherited field written into the hash that
+/// inherits it.
+///
+/// Each hash keeps its own members, in order, followed by those it
+/// inherits in the order [`yaml_field_parent`](crate::yaml_path::yaml_field_parent)
+/// searches its ancestors, the first found winning.  Inherited values are
+/// flattened too, relative references inside them resolving from where
+/// they were written.  `parent` references are paths from `yaml`.
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::{Yaml, YamlLoader};
+/// use aep_rust_common::flatten::{flatten, FlattenOptions};
+/// let s = r"---
+/// base:
+///   width: 32
+///   description: a word
+/// reg:
+///   parent: base
+///   offset: 4
+/// " ;
+/// let yaml = &YamlLoader::load_from_str(s).unwrap()[0] ;
+/// let flat = flatten(yaml, FlattenOptions::default()).unwrap() ;
+/// assert_eq!(flat["reg"]["width"], Yaml::Integer(32)) ;
+/// assert_eq!(flat["reg"]["parent"], Yaml::BadValue) ;
+///
+/// let bare = flatten(yaml, FlattenOptions { keep_parent: true, keep_description: false }).unwrap() ;
+/// assert_eq!(bare["reg"]["parent"].as_str(), Some("base")) ;
+/// assert_eq!(bare["reg"]["description"], Yaml::BadValue) ;
+/// ```
+///
+pub fn flatten(yaml: &Yaml, options: FlattenOptions) -> Result<Yaml, YamlPathError> {
+    let ancestors = Ancestors { root: yaml, ap: &BashArrayParser::new(), max_depth: DEFAULT_MAX_PARENT_DEPTH, files: None, mounts: Vec::new() };
+    flatten_with(&ancestors, yaml, YamlPath::new(), options)
+}
+
+///
+/// Flatten `node`, found at `path`, resolving `parent` references with
+/// `ancestors`.  A hash that inherits itself, `b.x` inheriting `a` which
+/// inherits `b`, would flatten forever and is a
+/// [`YamlPathError::ParentCycle`].
+///
+pub(crate) fn flatten_with<'a>(ancestors: &Ancestors<'a, '_>, node: &'a Yaml, path: YamlPath, options: FlattenOptions) -> Result<Yaml, YamlPathError> {
+    flatten_node(ancestors, node, path, options, &mut Vec::new())
+}
+
+// `active` holds the hashes being flattened, outermost first
+fn flatten_node<'a>(ancestors: &Ancestors<'a, '_>, node: &'a Yaml, path: YamlPath, options: FlattenOptions,
+                    active: &mut Vec<(*const Yaml, YamlPath)>) -> Result<Yaml, YamlPathError> {
+    match node {
+        Yaml::Hash(_) => {
+            if let Some(start) = active.iter().position(|(n, _)| std::ptr::eq(*n, node)) {
+                let name = |p: &YamlPath| if p.is_empty() { String::from("$") } else { ancestors.ap.format_path(p) };
+                let mut chain: Vec<String> = active[start..].iter().map(|(_, p)| name(p)).collect();
+                chain.push(name(&path));
+                return Err(YamlPathError::ParentCycle { path: chain.join(" -> "), segment: String::from("parent") });
+            }
+            let parent_key = Yaml::String(String::from("parent"));
+            let description_key = Yaml::String(String::from("description"));
+            // each member with the path of the hash it was found in
+            let members: RefCell<Vec<(&'a Yaml, &'a Yaml, YamlPath)>> = RefCell::new(Vec::new());
+            let own = node.as_hash().map(|h| h as *const Hash);
+            ancestors.search_at(node, path.clone(), &|h, holder| {
+                let mut members = members.borrow_mut();
+                for (k, v) in h {
+                    let inherited = own != Some(h as *const Hash);
+                    if (*k == parent_key && (inherited || !options.keep_parent))
+                        || (*k == description_key && !options.keep_description)
+                        || members.iter().any(|(m, _, _)| *m == k) {
+                        continue;
+                    }
+                    members.push((k, v, holder.cloned().unwrap_or_default()));
+                }
+                Ok(None::<()>)
+            })?;
+
+            active.push((node, path));
+            let mut flat = Hash::new();
+            for (k, v, mut holder) in members.into_inner() {
+                holder.push(Segment::Key(key_text(k)));
+                flat.insert(k.clone(), flatten_node(ancestors, v, holder, options, active)?);
+            }
+            active.pop();
+            Ok(Yaml::Hash(flat))
+        }
+        Yaml::Array(a) => {
+            let mut flat = Vec::with_capacity(a.len());
+            for (i, v) in a.iter().enumerate() {
+                let mut element = path.clone();
+                element.push(Segment::Index(i));
+                flat.push(flatten_node(ancestors, v, element, options, active)?);
+            }
+            Ok(Yaml::Array(flat))
+        }
+        _ => Ok(node.clone()),
+    }
+}
+
+/// a hash key as a path segment
+fn key_text(key: &Yaml) -> String {
+    match key {
+        Yaml::String(s) => s.to_string(),
+        Yaml::Integer(i) => i.to_string(),
+        Yaml::Real(r) => r.to_string(),
+        Yaml::Boolean(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
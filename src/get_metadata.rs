@@ -21,82 +21,226 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 // 
+use crate::error::YamlPathError;
 use crate::yaml_descender::YamlDescender;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use crate::previous::PreviousAction;
 use std::string::String;
 use yaml_rust::Yaml;
 
+///
+/// The `completion-metadata` block of a document
+///
+/// ```yaml
+/// completion-metadata:
+///   root: dataroot           # completion starts below this key
+///   terminal-fields: [bits]  # a hash holding one of these is a leaf
+///   terminus: reset          # as terminal-fields, for a single key
+///   ignore-fields: [notes]   # hidden as well as the defaults
+///   descriptions: true       # offer descriptions when none were asked for
+///   previous:                # what follows a command-line word
+///     "-f": {action: reload}
+/// ```
+///
+/// See [`PreviousAction`] for the actions `previous` may hold.  Every key
+/// is optional.  `parent`, `description` and `completion` are always
+/// hidden from completions, `ignore-fields` adds to them.
+///
+#[derive(Debug, Clone, PartialEq)]
 pub struct Metadata {
-    pub root: Yaml,
+    /// the top level key completion starts below, empty for the document
+    pub root: String,
+    pub terminal_fields: HashSet<Yaml>,
+    /// a single terminal field, `Yaml::BadValue` when not set
     pub terminus: Yaml,
+    /// keys that are never offered as completions
     pub ignore_fields: HashSet<String>,
     pub get_descriptions: bool,
-    
-    has_terminus: bool
+    /// the action for each command-line word, see [`PreviousAction`]
+    pub previous: HashMap<String, PreviousAction>,
 }
 
-impl Metadata {
-    fn new(root: Yaml, terminus: Yaml, ignores: Vec<&Yaml>, get_descriptions: bool) -> Self {
-        let h: HashSet<String> = ignores.into_iter()
-            .map(|y| y.as_str().unwrap().to_string())
-            .collect();
-        let has_terminus = terminus.as_str().is_some();
+impl Default for Metadata {
+    fn default() -> Self {
         Metadata {
-            root: root,
-            terminus: terminus,
-            ignore_fields: h,
-            get_descriptions: get_descriptions,
-            has_terminus: has_terminus,
+            root: String::new(),
+            terminal_fields: HashSet::new(),
+            terminus: Yaml::BadValue,
+            ignore_fields: HashSet::from([String::from("parent"), String::from("description"), String::from("completion")]),
+            get_descriptions: false,
+            previous: HashMap::new(),
         }
     }
-    
-    fn default() -> Self {
-        let root = Yaml::BadValue;
-        let terminus = Yaml::BadValue;
-        Metadata::new(root, terminus, Vec::new(), false)
+}
+
+// the value of `key` in `metadata`, TypeMismatch unless `accept` converts it
+fn field<T>(metadata: &Yaml, key: &str, expected: &'static str, accept: impl Fn(&Yaml) -> Option<T>) -> Result<Option<T>, YamlPathError> {
+    match &metadata[key] {
+        Yaml::BadValue => Ok(None),
+        y => accept(y).map(Some).ok_or_else(|| YamlPathError::TypeMismatch {
+            path: String::from("completion-metadata"),
+            segment: key.to_string(),
+            expected
+        }),
     }
-    
+}
+
+fn strings(y: &Yaml) -> Option<Vec<String>> {
+    y.as_vec()?.iter().map(|s| s.as_str().map(str::to_string)).collect()
+}
+
+// a word in `previous`, which must be a string
+fn previous_word(word: &Yaml) -> Result<String, YamlPathError> {
+    word.as_str().map(str::to_string).ok_or_else(|| YamlPathError::TypeMismatch {
+        path: String::from("completion-metadata.previous"),
+        segment: format!("{:?}", word),
+        expected: "a string key"
+    })
+}
+
+/// a key `completion-metadata` may hold, what it must be and a test
+type KeySchema = (&'static str, &'static str, fn(&Yaml) -> bool);
+
+const SCHEMA: &[KeySchema] = &[
+    ("root", "a string", |y| y.as_str().is_some()),
+    ("terminal-fields", "an array", |y| y.as_vec().is_some()),
+    ("terminus", "a string", |y| y.as_str().is_some()),
+    ("ignore-fields", "a list of strings", |y| strings(y).is_some()),
+    ("descriptions", "a bool", |y| y.as_bool().is_some()),
+    ("previous", "a hash", |y| y.as_hash().is_some()),
+];
+
+impl Metadata {
+    ///
+    /// Read the `completion-metadata` block of `doc`, the defaults when it
+    /// has none.  A key of the wrong type is a
+    /// [`YamlPathError::TypeMismatch`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::get_metadata::Metadata;
+    /// use yaml_rust::YamlLoader;
+    /// let doc = &YamlLoader::load_from_str("completion-metadata: {root: data, ignore-fields: [notes]}").unwrap()[0];
+    /// let metadata = Metadata::from_yaml(doc).unwrap();
+    /// assert_eq!(metadata.root, "data");
+    /// assert!(metadata.ignore_fields.contains("notes"));
+    /// assert!(metadata.ignore_fields.contains("parent"));
+    /// ```
+    ///
+    pub fn from_yaml(doc: &Yaml) -> Result<Metadata, YamlPathError> {
+        let mut metadata = Metadata::default();
+        let block = match doc {
+            Yaml::Hash(h) => match h.get(&Yaml::String(String::from("completion-metadata"))) {
+                Some(b) => b,
+                None => return Ok(metadata)
+            },
+            _ => return Ok(metadata)
+        } ;
+        if let Some(root) = field(block, "root", "a string", |y| y.as_str().map(str::to_string))? {
+            metadata.root = root;
+        }
+        if let Some(fields) = field(block, "terminal-fields", "an array", |y| y.as_vec().cloned())? {
+            metadata.terminal_fields = fields.into_iter().collect();
+        }
+        if let Some(terminus) = field(block, "terminus", "a string", |y| y.as_str().map(|_| y.clone()))? {
+            metadata.terminus = terminus;
+        }
+        if let Some(ignores) = field(block, "ignore-fields", "a list of strings", strings)? {
+            metadata.ignore_fields.extend(ignores);
+        }
+        if let Some(descriptions) = field(block, "descriptions", "a bool", Yaml::as_bool)? {
+            metadata.get_descriptions = descriptions;
+        }
+        if let Some(previous) = field(block, "previous", "a hash", |y| y.as_hash().cloned())? {
+            for (word, action) in previous {
+                let word = previous_word(&word)?;
+                metadata.previous.insert(word.clone(), PreviousAction::from_yaml(&word, &action)?);
+            }
+        }
+        Ok(metadata)
+    }
+
+    ///
+    /// Check the `completion-metadata` block of the first of `docs` for
+    /// unknown keys, values of the wrong type, `previous` entries that are
+    /// not valid actions and a `root` missing from the documents holding
+    /// data.  Every problem is returned, each naming
+    /// the offending key; an empty list means the block is valid or absent.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::get_metadata::Metadata;
+    /// use yaml_rust::YamlLoader;
+    /// let docs = YamlLoader::load_from_str("completion-metadata: {terminal-feilds: [a], root: 3}").unwrap();
+    /// let problems = Metadata::validate(&docs);
+    /// assert_eq!(problems.len(), 2);
+    /// assert_eq!(problems[0].to_string(), "terminal-feilds is not a known key of completion-metadata");
+    /// assert_eq!(problems[1].to_string(), "completion-metadata.root is not a string");
+    /// ```
+    ///
+    pub fn validate(docs: &[Yaml]) -> Vec<YamlPathError> {
+        let key = Yaml::String(String::from("completion-metadata"));
+        let block = match docs.first().and_then(Yaml::as_hash).and_then(|h| h.get(&key)) {
+            Some(Yaml::Hash(h)) => h,
+            Some(_) => return vec![YamlPathError::TypeMismatch { path: String::new(), segment: String::from("completion-metadata"), expected: "a hash" }],
+            None => return Vec::new()
+        } ;
+        let mut problems = Vec::new();
+        for (k, v) in block {
+            let name = match k.as_str() {
+                Some(name) => name,
+                None => {
+                    problems.push(YamlPathError::TypeMismatch { path: String::from("completion-metadata"), segment: format!("{:?}", k), expected: "a string key" });
+                    continue;
+                }
+            } ;
+            match SCHEMA.iter().find(|(key, _, _)| *key == name) {
+                None => problems.push(YamlPathError::UnknownKey { path: String::from("completion-metadata"), segment: name.to_string() }),
+                Some((_, expected, valid)) if !valid(v) => problems.push(YamlPathError::TypeMismatch {
+                    path: String::from("completion-metadata"),
+                    segment: name.to_string(),
+                    expected
+                }),
+                Some(_) => ()
+            }
+        }
+        if let Some(Yaml::Hash(previous)) = block.get(&Yaml::String(String::from("previous"))) {
+            for (word, action) in previous {
+                if let Err(e) = previous_word(word).and_then(|w| PreviousAction::from_yaml(&w, action)) {
+                    problems.push(e);
+                }
+            }
+        }
+        // a leading document of nothing but metadata holds no data
+        let data = if docs.len() > 1 && docs[0].as_hash().is_some_and(|h| h.len() == 1) { &docs[1..] } else { docs } ;
+        if let Some(Yaml::String(root)) = block.get(&Yaml::String(String::from("root")))
+            && !root.is_empty()
+            && data.iter().any(|d| d.as_hash().is_none_or(|h| !h.contains_key(&Yaml::String(root.clone())))) {
+            problems.push(YamlPathError::KeyNotFound { path: String::from("completion-metadata.root"), segment: root.clone() });
+        }
+        problems
+    }
+
     pub fn has_root(&self) -> bool {
-        self.root.as_str().is_some()
+        !self.root.is_empty()
     }
-    
+
     pub fn has_terminus(&self) -> bool {
-        self.has_terminus
+        self.terminus.as_str().is_some()
     }
+
+    /// whether `yaml` is a hash holding the terminus or a terminal field
     pub fn has_terminal_field(&self, yaml: &Yaml) -> bool {
-        self.has_terminus && match yaml {
-            Yaml::Hash(h) => {
-                 h.contains_key(&self.terminus)
-            },
-            _ => { false }
+        match yaml {
+            Yaml::Hash(h) => h.keys().any(|k| self.terminal_fields.contains(k) || (self.has_terminus() && *k == self.terminus)),
+            _ => false
         }
     }
 }
 
 impl YamlDescender {
-    
-    pub fn get_metadata(&self, doc: &Yaml, get_descriptions: bool) -> Metadata {
-        let metadata_r = self.get_field_or_parent(doc,"completion-metadata") ;
-        let metadata = match metadata_r {
-            Ok(y) => y,
-            Err(_) => return Metadata::default()
-        } ;
-
-        let root = self.get_field_or_parent(&metadata, "root").unwrap_or_else(|_| Yaml::BadValue);
-
-        let terminus = self.get_field_or_parent(&metadata, "root").unwrap_or_else(|_| Yaml::BadValue);
-
-
-        // let ignore_fields = match self.get_field_or_parent(&metadata, "ignore_fields") {
-        //     Ok(y) => {
-        //         match y {
-        //             Yaml::Array(arr) => arr.iter().collect(),
-        //             _ => Vec::new()
-        //         }
-        //     },
-        //     Err(_) => Vec::new()
-        // };
-        Metadata::new(root.clone(), terminus.clone(),
-                      Vec::new(), get_descriptions)
+    /// the `completion-metadata` read when the descender was created
+    pub fn get_metadata(&self) -> &Metadata {
+        &self.metadata
     }
-}
\ No newline at end of file
+}
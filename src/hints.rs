@@ -0,0 +1,186 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::io::Write;
+use yaml_rust::Yaml;
+use crate::completion::Completion;
+use crate::error::YamlPathError;
+use crate::matcher::rank;
+use crate::path::scalar_text;
+use crate::previous::file_candidates;
+use crate::yaml_descender::YamlDescender;
+
+///
+/// How to complete the value of a node, from its `completion` key, which
+/// is inherited through `parent` like any other field
+///
+/// ```yaml
+/// mode:
+///   completion: {type: enum, values: [fast, slow]}
+/// config:
+///   completion: {type: file}
+/// output:
+///   completion: {type: dir}
+/// pin:
+///   completion: {type: ref, from: GPIO.pins}   # a key of GPIO.pins
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueHint {
+    /// one of a fixed list of words
+    Enum(Vec<String>),
+    /// a file or directory name
+    File,
+    /// a directory name
+    Dir,
+    /// a key of the hash, or an item of the array, at this path from the
+    /// root, in the descender's dialect
+    Ref(String),
+}
+
+impl ValueHint {
+    /// read a `completion` value, errors naming the offending key
+    pub fn from_yaml(yaml: &Yaml) -> Result<ValueHint, YamlPathError> {
+        let mismatch = |segment: &str, expected: &'static str| YamlPathError::TypeMismatch { path: String::from("completion"), segment: segment.to_string(), expected };
+        if yaml.as_hash().is_none() {
+            return Err(YamlPathError::TypeMismatch { path: String::new(), segment: String::from("completion"), expected: "a hash" });
+        }
+        match yaml["type"].as_str().ok_or_else(|| mismatch("type", "a string"))? {
+            "file" => Ok(ValueHint::File),
+            "dir" => Ok(ValueHint::Dir),
+            "enum" => yaml["values"].as_vec()
+                .and_then(|v| v.iter().map(|s| s.as_str().map(str::to_string)).collect::<Option<Vec<String>>>())
+                .map(ValueHint::Enum)
+                .ok_or_else(|| mismatch("values", "a list of strings")),
+            "ref" => yaml["from"].as_str()
+                .map(|f| ValueHint::Ref(f.to_string()))
+                .ok_or_else(|| mismatch("from", "a string")),
+            _ => Err(mismatch("type", "one of enum, file, dir or ref")),
+        }
+    }
+}
+
+impl YamlDescender {
+    ///
+    /// The hint for completing the value of the node at `path`, `None`
+    /// when neither it nor its ancestors have one.  A `path` with a filter,
+    /// `pins[name=a].mode`, takes the first node it selects.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::hints::ValueHint;
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// modes:
+    ///   completion: {type: enum, values: [fast, slow]}
+    /// uart:
+    ///   mode: {parent: modes}
+    /// " ;
+    /// let descender = YamlDescender::new(s, true).unwrap() ;
+    /// assert_eq!(descender.value_hint("uart.mode").unwrap(), Some(ValueHint::Enum(vec!["fast".to_string(), "slow".to_string()]))) ;
+    /// let mut out = Vec::new() ;
+    /// descender.write_value_completions(&mut out, "uart.mode", "s").unwrap() ;
+    /// assert_eq!(String::from_utf8(out).unwrap(), "slow\n") ;
+    /// ```
+    ///
+    pub fn value_hint(&self, path: &str) -> Result<Option<ValueHint>, YamlPathError> {
+        let node = match self.yaml_descend_path(path) {
+            Ok(node) => node,
+            Err(e) => match self.yaml_query(path).ok().and_then(|nodes| nodes.first().map(|&(_, node)| node)) {
+                Some(node) => node,
+                None => return Err(e),
+            }
+        };
+        if node.as_hash().is_none() {
+            return Ok(None);
+        }
+        match self.get_field_or_parent(node, "completion") {
+            Ok(hint) => ValueHint::from_yaml(&hint).map(Some).map_err(|e| e.with_path(path)),
+            Err(YamlPathError::KeyNotFound { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// the values `hint` offers that match `prefix`, best first
+    pub(crate) fn value_candidates(&self, hint: &ValueHint, prefix: &str) -> Vec<String> {
+        let candidates: Vec<String> = match hint {
+            ValueHint::File => return file_candidates(prefix, false),
+            ValueHint::Dir => return file_candidates(prefix, true),
+            ValueHint::Enum(values) => values.clone(),
+            ValueHint::Ref(from) => {
+                let node = self.ap.parse_reference(from).and_then(|path| self.yaml_descend_path(&path));
+                match node {
+                    Ok(Yaml::Hash(h)) => h.keys().filter_map(Yaml::as_str)
+                        .filter(|k| !self.metadata.ignore_fields.contains(*k))
+                        .map(str::to_string).collect(),
+                    Ok(Yaml::Array(a)) => a.iter().filter_map(scalar_text).collect(),
+                    _ => Vec::new()
+                }
+            }
+        } ;
+        rank(candidates, String::as_str, prefix, &self.matchers)
+    }
+
+    ///
+    /// Complete `partial`, a value being typed for the node at `path`,
+    /// from the node's [`ValueHint`].  Nothing is written for a node
+    /// without one.
+    ///
+    pub fn write_value_completions(&self, writer: &mut dyn Write, path: &str, partial: &str) -> std::io::Result<()> {
+        self.render(writer, Ok(self.hinted(path, partial, "").unwrap_or_default()), false)
+    }
+
+    // the candidates for `partial` after `prefix`, `None` when `path` has
+    // no hint
+    pub(crate) fn hinted(&self, path: &str, partial: &str, prefix: &str) -> Option<Vec<Completion>> {
+        let hint = self.value_hint(path).ok()??;
+        Some(self.value_candidates(&hint, partial).into_iter().map(|c| Completion::word(format!("{}{}", prefix, c))).collect())
+    }
+}
+
+///
+/// Split `input` as `path=value` at the last `=` outside brackets and
+/// quotes, so `pins[name=a].mode=f` is a value for `pins[name=a].mode`.
+///
+pub(crate) fn split_value(input: &str) -> Option<(&str, &str)> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut split = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.saturating_sub(1),
+            (None, '=') if depth == 0 => split = Some(i),
+            _ => {}
+        }
+    }
+    split.map(|i| (&input[..i], &input[i + 1..]))
+}
@@ -0,0 +1,336 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use yaml_rust::{Yaml, YamlLoader};
+use yaml_rust::yaml::Hash;
+use crate::arrayparser::{ArrayParser, BashArrayParser};
+use crate::error::YamlPathError;
+use crate::path::{scalar_text, Segment, YamlPath};
+use crate::yaml_path::{all_nodes, descend, parent_references};
+
+///
+/// Load every document in the file at `path`, mounting the files named by
+/// `include` keys.
+///
+/// A hash holding `include: other.yaml`, or a list of files, gets the keys
+/// of each file's first document that it does not already have, earlier
+/// files winning over later ones.  `other.yaml#GPIO.words` mounts only the
+/// node at that path, and an include in a hash with no other keys may name
+/// an array or a scalar.  Relative file names are found from the
+/// directory of the file holding the `include`.  A file that ends up
+/// including itself is an error naming the chain of files.
+///
+/// `parent` references to other files, `common.yaml#GPIO.words`, are kept
+/// as written.  A descender created from the file remembers which file each
+/// mounted node came from, so they still name the same file.
+///
+/// ```rust
+/// use aep_rust_common::include::load_file;
+/// let dir = std::env::temp_dir().join("aep-include-doc");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(dir.join("pins.yaml"), "set0: {description: set pins}\n").unwrap();
+/// std::fs::write(dir.join("main.yaml"), "GPIO:\n  include: pins.yaml\n  base: 0x1000\n").unwrap();
+/// let docs = load_file(dir.join("main.yaml")).unwrap();
+/// assert_eq!(docs[0]["GPIO"]["set0"]["description"].as_str(), Some("set pins"));
+/// assert_eq!(docs[0]["GPIO"]["base"].as_i64(), Some(0x1000));
+/// ```
+///
+pub fn load_file(path: impl AsRef<Path>) -> Result<Vec<Yaml>, YamlPathError> {
+    Ok(load_file_mounted(path)?.into_iter().map(|(doc, _)| doc).collect())
+}
+
+/// [`load_file`] with the directory each part of each document was read from
+pub(crate) fn load_file_mounted(path: impl AsRef<Path>) -> Result<Vec<(Yaml, Mounts)>, YamlPathError> {
+    load_included(path.as_ref(), &mut Vec::new())
+}
+
+///
+/// Where the parts of a loaded document came from, relative references
+/// below them being found from these directories.  The longest path that
+/// starts a node's path names its directory, and the empty path is always
+/// present.
+///
+pub(crate) type Mounts = Vec<(YamlPath, PathBuf)>;
+
+/// the directory of the node at `path`
+pub(crate) fn mount_dir<'m>(mounts: &'m Mounts, path: &YamlPath) -> Option<&'m Path> {
+    mounts.iter()
+        .filter(|(prefix, _)| path.segments().starts_with(prefix.segments()))
+        .max_by_key(|(prefix, _)| prefix.segments().len())
+        .map(|(_, dir)| dir.as_path())
+}
+
+/// `mounts` for the node at `under`, which becomes the empty path
+pub(crate) fn rebase(mounts: &Mounts, under: &YamlPath) -> Mounts {
+    let n = under.segments().len();
+    let mut rebased: Mounts = mount_dir(mounts, under).map(|dir| (YamlPath::new(), dir.to_path_buf())).into_iter().collect();
+    for (prefix, dir) in mounts {
+        if prefix.segments().len() > n && prefix.segments().starts_with(under.segments()) {
+            rebased.push((YamlPath::from(prefix.segments()[n..].to_vec()), dir.clone()));
+        }
+    }
+    rebased
+}
+
+// `mounts` for a node mounted at `at`
+fn mounted_at(mounts: &Mounts, at: &[Segment]) -> impl Iterator<Item = (YamlPath, PathBuf)> {
+    mounts.iter().map(move |(prefix, dir)| (YamlPath::from([at, prefix.segments()].concat()), dir.clone()))
+}
+
+/// parse `source`, errors naming `file`
+pub(crate) fn load_str(source: &str, file: &str) -> Result<Vec<Yaml>, YamlPathError> {
+    YamlLoader::load_from_str(source).map_err(|e| YamlPathError::ParseError {
+        path: file.to_string(),
+        segment: format!("{}:{}", e.marker().line(), e.marker().col()),
+        message: e.to_string()
+    })
+}
+
+// the documents of `path` with their includes mounted, `stack` holding the
+// files that are including it
+fn load_included(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Vec<(Yaml, Mounts)>, YamlPathError> {
+    let file = path.display().to_string();
+    let canonical = path.canonicalize().map_err(|e| YamlPathError::ParseError { path: file.clone(), segment: String::new(), message: e.to_string() })?;
+    if stack.contains(&canonical) {
+        let start = stack.iter().position(|p| *p == canonical).unwrap();
+        let mut names: Vec<String> = stack[start..].iter().map(|p| p.display().to_string()).collect();
+        names.push(canonical.display().to_string());
+        return Err(YamlPathError::ParseError {
+            path: file,
+            segment: String::from("include"),
+            message: format!("include cycle {}", names.join(" -> "))
+        });
+    }
+    let source = std::fs::read_to_string(&canonical).map_err(|e| YamlPathError::ParseError { path: file.clone(), segment: String::new(), message: e.to_string() })?;
+    let docs = load_str(&source, &file)?;
+    let dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+    stack.push(canonical);
+    let expanded = docs.into_iter().map(|mut doc| {
+        let mut mounts = vec![(YamlPath::new(), dir.clone())];
+        expand_includes(&mut doc, &dir, &file, stack, &mut Vec::new(), &mut mounts).map(|_| (doc, mounts))
+    }).collect();
+    stack.pop();
+    expanded
+}
+
+///
+/// Mount the files named by every `include` below `yaml`, which was read
+/// from `file` in `dir` and is at `at` in its document, see [`load_file`].
+/// Where each mounted node came from is added to `mounts`.
+///
+pub(crate) fn expand_includes(yaml: &mut Yaml, dir: &Path, file: &str, stack: &mut Vec<PathBuf>,
+                              at: &mut Vec<Segment>, mounts: &mut Mounts) -> Result<(), YamlPathError> {
+    let include = Yaml::String(String::from("include"));
+    if let Yaml::Hash(h) = yaml && let Some(spec) = h.remove(&include) {
+        let mut mounted = Vec::new();
+        for reference in include_references(&spec, file)? {
+            mounted.push(mount(reference, dir, stack)?);
+        }
+        *yaml = overlay_includes(std::mem::take(h), mounted, at, mounts, file)?;
+    }
+    let mut below = |segment: Segment, v: &mut Yaml| {
+        at.push(segment);
+        let expanded = expand_includes(v, dir, file, stack, at, mounts);
+        at.pop();
+        expanded
+    };
+    match yaml {
+        Yaml::Hash(h) => h.iter_mut().try_for_each(|(k, v)| below(Segment::Key(scalar_text(k).unwrap_or_default()), v)),
+        Yaml::Array(a) => a.iter_mut().enumerate().try_for_each(|(i, v)| below(Segment::Index(i), v)),
+        _ => Ok(()),
+    }
+}
+
+fn include_references<'a>(spec: &'a Yaml, file: &str) -> Result<Vec<&'a str>, YamlPathError> {
+    let mismatch = || YamlPathError::TypeMismatch { path: file.to_string(), segment: String::from("include"), expected: "a string or a list of strings" };
+    match spec {
+        Yaml::String(s) => Ok(vec![s.as_str()]),
+        Yaml::Array(a) => a.iter().map(|p| p.as_str().ok_or_else(mismatch)).collect(),
+        _ => Err(mismatch()),
+    }
+}
+
+// the node one `include` reference names, its own includes mounted, and
+// where its parts came from
+fn mount(reference: &str, dir: &Path, stack: &mut Vec<PathBuf>) -> Result<(Yaml, Mounts), YamlPathError> {
+    let (name, path) = reference.split_once('#').unwrap_or((reference, ""));
+    let file = dir.join(name);
+    let mut docs = load_included(&file, stack)?;
+    if docs.is_empty() {
+        return Err(YamlPathError::ParseError { path: file.display().to_string(), segment: String::new(), message: String::from("no YAML documents") });
+    }
+    let (doc, mounts) = docs.swap_remove(0);
+    if path.is_empty() {
+        return Ok((doc, mounts));
+    }
+    let ap = BashArrayParser::new();
+    let path = ap.parse_reference(path)?;
+    let node = descend(&doc, &path, &ap).cloned().map_err(|e| YamlPathError::ParseError {
+        path: file.display().to_string(),
+        segment: reference.to_string(),
+        message: e.to_string()
+    })?;
+    Ok((node, rebase(&mounts, &path)))
+}
+
+// `local`, at `at`, with the keys of each mounted hash it does not already
+// have, adding where the mounted keys came from to `mounts`
+fn overlay_includes(local: Hash, mounted: Vec<(Yaml, Mounts)>, at: &[Segment], mounts: &mut Mounts, file: &str) -> Result<Yaml, YamlPathError> {
+    if local.is_empty() && mounted.len() == 1 {
+        let (yaml, from) = mounted.into_iter().next().unwrap();
+        mounts.extend(mounted_at(&from, at));
+        return Ok(yaml);
+    }
+    let mut merged = local;
+    for (yaml, from) in mounted {
+        let Yaml::Hash(h) = yaml else {
+            return Err(YamlPathError::TypeMismatch { path: file.to_string(), segment: String::from("include"), expected: "a hash to merge with other keys" });
+        };
+        for (k, v) in h {
+            if merged.contains_key(&k) {
+                continue;
+            }
+            let key = YamlPath::from(vec![Segment::Key(scalar_text(&k).unwrap_or_default())]);
+            mounts.extend(mounted_at(&rebase(&from, &key), &[at, key.segments()].concat()));
+            merged.insert(k, v);
+        }
+    }
+    Ok(Yaml::Hash(merged))
+}
+
+///
+/// Where a `parent` reference names another file, `common.yaml#GPIO.words`,
+/// the file and the path within it.  Only names ending `.yaml` or `.yml`
+/// are files, so `#` may still appear in keys.
+///
+pub(crate) fn split_file_reference(reference: &str) -> Option<(&str, &str)> {
+    let (file, path) = reference.split_once('#')?;
+    (file.ends_with(".yaml") || file.ends_with(".yml")).then_some((file, path))
+}
+
+///
+/// The files `parent` references lead to, loaded once when the tree is
+/// and looked up by the directory of the file holding the reference.  A
+/// file that cannot be loaded is only an error when a lookup reaches it.
+///
+#[derive(Default)]
+pub(crate) struct Files {
+    // the directory references in the main tree are relative to
+    base: PathBuf,
+    // where the parts of each main document came from, by index
+    main: Vec<Mounts>,
+    // the first document of each file, by canonical path, and where its
+    // parts came from
+    trees: HashMap<PathBuf, (Yaml, Mounts)>,
+    // a directory joined with a reference, to the canonical path
+    aliases: HashMap<PathBuf, Result<PathBuf, YamlPathError>>,
+}
+
+impl Files {
+    /// the files reached from the `parent` references in `docs`, relative
+    /// to `base` or to the directories in `main`, one [`Mounts`] per document
+    pub fn load(base: PathBuf, docs: &[Yaml], main: Vec<Mounts>) -> Files {
+        let mut files = Files { base, main, ..Files::default() };
+        let mut pending: Vec<(PathBuf, String)> = Vec::new();
+        for (i, doc) in docs.iter().enumerate() {
+            file_references(doc, files.main.get(i), &files.base, &mut pending);
+        }
+        while let Some((dir, name)) = pending.pop() {
+            let joined = dir.join(&name);
+            if files.aliases.contains_key(&joined) {
+                continue;
+            }
+            let loaded = joined.canonicalize()
+                .map_err(|e| YamlPathError::ParseError { path: joined.display().to_string(), segment: String::new(), message: e.to_string() })
+                .and_then(|canonical| {
+                    if !files.trees.contains_key(&canonical) {
+                        let dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+                        let (doc, mounts) = load_file_mounted(&canonical)?.into_iter().next()
+                            .unwrap_or((Yaml::Null, vec![(YamlPath::new(), dir.clone())]));
+                        file_references(&doc, Some(&mounts), &dir, &mut pending);
+                        files.trees.insert(canonical.clone(), (doc, mounts));
+                    }
+                    Ok(canonical)
+                });
+            files.aliases.insert(joined, loaded);
+        }
+        files
+    }
+
+    /// the directory references held in `tree` are relative to, the main
+    /// tree being `None`, when it holds nothing mounted from elsewhere
+    pub fn dir_of<'a>(&'a self, tree: Option<&'a Path>) -> &'a Path {
+        tree.and_then(Path::parent).unwrap_or(&self.base)
+    }
+
+    /// where the parts of the main document `index` came from
+    pub fn main_mounts(&self, index: usize) -> Option<&Mounts> {
+        self.main.get(index)
+    }
+
+    /// where the parts of the other file `tree` came from
+    pub fn mounts_of(&self, tree: &Path) -> Option<&Mounts> {
+        self.trees.get(tree).map(|(_, mounts)| mounts)
+    }
+
+    /// the canonical name and root of the file `name` from `dir`
+    pub fn find(&self, dir: &Path, name: &str) -> Result<(&Path, &Yaml), YamlPathError> {
+        let joined = dir.join(name);
+        match self.aliases.get(&joined) {
+            Some(Ok(canonical)) => {
+                let (path, (tree, _)) = self.trees.get_key_value(canonical).unwrap();
+                Ok((path.as_path(), tree))
+            }
+            Some(Err(e)) => Err(e.clone()),
+            None => Err(YamlPathError::ParseError { path: joined.display().to_string(), segment: String::new(), message: String::from("file was not loaded") }),
+        }
+    }
+
+    /// the root of `tree`, `main` being the main tree's
+    pub fn root_of<'a>(&'a self, tree: Option<&Path>, main: &'a Yaml) -> &'a Yaml {
+        tree.and_then(|t| self.trees.get(t)).map(|(tree, _)| tree).unwrap_or(main)
+    }
+
+    /// the loaded file holding `node` at `path`, if any
+    pub fn tree_holding(&self, node: &Yaml, path: &YamlPath) -> Option<&Path> {
+        let ap = BashArrayParser::new();
+        self.trees.iter()
+            .find(|(_, (tree, _))| descend(tree, path, &ap).is_ok_and(|n| std::ptr::eq(n, node)))
+            .map(|(p, _)| p.as_path())
+    }
+}
+
+// queue each file named by a `parent` reference below `yaml`, found from
+// the directory `mounts` gives, or else `dir`
+fn file_references(yaml: &Yaml, mounts: Option<&Mounts>, dir: &Path, pending: &mut Vec<(PathBuf, String)>) {
+    for (path, node) in all_nodes(yaml) {
+        if let Yaml::Hash(h) = node && let Ok(references) = parent_references(h) {
+            let dir = mounts.and_then(|m| mount_dir(m, &path)).unwrap_or(dir);
+            for (name, _) in references.into_iter().filter_map(split_file_reference) {
+                pending.push((dir.to_path_buf(), name.to_string()));
+            }
+        }
+    }
+}
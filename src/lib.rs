@@ -22,12 +22,24 @@
 // SOFTWARE.
 // 
 mod rust_common;
+pub mod error;
 pub mod yaml_descender;
 pub mod find_config_file;
 pub mod descender;
-mod get_metadata;
+pub mod get_metadata;
+#[cfg(test)]
 mod unittests;
+#[cfg(test)]
 mod strwriter;
 pub mod yaml_path;
-mod arrayparser;
+pub mod arrayparser;
+pub mod path;
+pub mod flatten;
+pub mod provenance;
+pub mod merge;
+pub mod include;
+pub mod previous;
+pub mod hints;
+pub mod completion;
+pub mod matcher;
 
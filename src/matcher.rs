@@ -0,0 +1,180 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+
+///
+/// Decides whether a candidate matches what has been typed, and how well
+///
+/// `score` returns `None` for no match, otherwise higher scores rank
+/// first.  Candidates with equal scores are ranked by name.
+///
+pub trait Matcher: Send + Sync {
+    fn score(&self, candidate: &str, pattern: &str) -> Option<i64>;
+}
+
+/// `candidate` starts with `pattern`, today's behavior
+#[derive(Default)]
+pub struct PrefixMatcher {
+}
+
+/// `candidate` starts with `pattern` ignoring case, an exact prefix ranking
+/// first
+#[derive(Default)]
+pub struct IgnoreCasePrefixMatcher {
+}
+
+/// `pattern` appears anywhere in `candidate` ignoring case, earlier ranking
+/// first
+#[derive(Default)]
+pub struct SubstringMatcher {
+}
+
+///
+/// The characters of `pattern` appear in order in `candidate` ignoring
+/// case, as fzf matches.  Consecutive characters and characters starting a
+/// word, after `.`, `-`, `_` or a change to upper case, score more, gaps
+/// score less.
+///
+#[derive(Default)]
+pub struct SubsequenceMatcher {
+}
+
+impl Matcher for PrefixMatcher {
+    fn score(&self, candidate: &str, pattern: &str) -> Option<i64> {
+        candidate.starts_with(pattern).then_some(0)
+    }
+}
+
+impl Matcher for IgnoreCasePrefixMatcher {
+    fn score(&self, candidate: &str, pattern: &str) -> Option<i64> {
+        if candidate.starts_with(pattern) {
+            return Some(1);
+        }
+        candidate.to_lowercase().starts_with(&pattern.to_lowercase()).then_some(0)
+    }
+}
+
+impl Matcher for SubstringMatcher {
+    fn score(&self, candidate: &str, pattern: &str) -> Option<i64> {
+        let position = candidate.to_lowercase().find(&pattern.to_lowercase())?;
+        Some(-(position as i64))
+    }
+}
+
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const WORD_START: i64 = 8;
+const GAP: i64 = 1;
+
+impl Matcher for SubsequenceMatcher {
+    fn score(&self, candidate: &str, pattern: &str) -> Option<i64> {
+        let chars: Vec<char> = candidate.chars().collect();
+        let mut score = 0;
+        let mut next = 0;
+        let mut last: Option<usize> = None;
+        for p in pattern.chars().flat_map(char::to_lowercase) {
+            let found = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(std::iter::once(p)))?;
+            score += MATCH;
+            if last.is_some_and(|l| l + 1 == found) {
+                score += CONSECUTIVE;
+            }
+            let starts_word = found == 0
+                || matches!(chars[found - 1], '.' | '-' | '_' | ' ')
+                || (chars[found].is_uppercase() && chars[found - 1].is_lowercase());
+            if starts_word {
+                score += WORD_START;
+            }
+            score -= GAP * (found - next) as i64;
+            last = Some(found);
+            next = found + 1;
+        }
+        Some(score)
+    }
+}
+
+///
+/// The built in ways of matching
+///
+/// - `Prefix`: an exact prefix
+/// - `IgnoreCase`: a prefix ignoring case
+/// - `Substring`: anywhere in the candidate, ignoring case
+/// - `Fuzzy`: an fzf style subsequence
+/// - `Fallback`: a prefix, or when nothing has one each looser mode in
+///   the order above, the default
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MatchMode {
+    Prefix,
+    IgnoreCase,
+    Substring,
+    Fuzzy,
+    #[default]
+    Fallback,
+}
+
+impl MatchMode {
+    /// the matchers tried in turn until one matches something
+    pub fn matchers(self) -> Vec<Box<dyn Matcher>> {
+        match self {
+            MatchMode::Prefix => vec![Box::new(PrefixMatcher::default())],
+            MatchMode::IgnoreCase => vec![Box::new(IgnoreCasePrefixMatcher::default())],
+            MatchMode::Substring => vec![Box::new(SubstringMatcher::default())],
+            MatchMode::Fuzzy => vec![Box::new(SubsequenceMatcher::default())],
+            MatchMode::Fallback => vec![
+                Box::new(PrefixMatcher::default()),
+                Box::new(IgnoreCasePrefixMatcher::default()),
+                Box::new(SubstringMatcher::default()),
+                Box::new(SubsequenceMatcher::default()),
+            ],
+        }
+    }
+}
+
+///
+/// The items whose `text` matches `pattern` under the first of `matchers`
+/// that matches any, best first and then by text
+///
+/// # Example
+/// ```rust
+/// use aep_rust_common::matcher::{rank, MatchMode};
+/// let keys = vec!["GPIO", "gpio_alt", "timer", "pio"];
+/// let matchers = MatchMode::Fallback.matchers();
+/// assert_eq!(rank(keys.clone(), |k| k, "gpio", &matchers), vec!["gpio_alt"]);
+/// assert_eq!(rank(keys.clone(), |k| k, "GP", &matchers), vec!["GPIO"]);
+/// assert_eq!(rank(keys.clone(), |k| k, "io", &matchers), vec!["pio", "GPIO", "gpio_alt"]);
+/// assert_eq!(rank(keys, |k| k, "tmr", &matchers), vec!["timer"]);
+/// ```
+///
+pub fn rank<T>(mut items: Vec<T>, text: impl Fn(&T) -> &str, pattern: &str, matchers: &[Box<dyn Matcher>]) -> Vec<T> {
+    for matcher in matchers {
+        let scores: Vec<Option<i64>> = items.iter().map(|item| matcher.score(text(item), pattern)).collect();
+        if scores.iter().all(Option::is_none) {
+            continue;
+        }
+        let mut matched: Vec<(i64, T)> = scores.into_iter().zip(items).filter_map(|(score, item)| Some((score?, item))).collect();
+        matched.sort_by(|(a, x), (b, y)| b.cmp(a).then_with(|| text(x).cmp(text(y))));
+        return matched.into_iter().map(|(_, item)| item).collect();
+    }
+    items.clear();
+    items
+}
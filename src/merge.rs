@@ -0,0 +1,119 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::cell::RefCell;
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
+use crate::error::YamlPathError;
+use crate::yaml_path::Ancestors;
+
+///
+/// How an inherited array combines with the one overriding it
+///
+/// - `Replace`: the nearer array wins whole
+/// - `Append`: the ancestor's elements followed by the nearer ones
+/// - `ByIndex`: elements at the same index are merged, the longer array's
+///   extra elements kept
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayMerge {
+    #[default]
+    Replace,
+    Append,
+    ByIndex,
+}
+
+///
+/// How a field found on several nodes of a `parent` chain is resolved
+///
+/// - `FirstFound`: the value on the nearest node, searched in the order
+///   described by [`YamlDescender::get_field_or_parent`](crate::yaml_descender::YamlDescender::get_field_or_parent)
+/// - `DeepMerge`: hashes are merged key by key all the way down the chain,
+///   nearer values winning, arrays combined as the [`ArrayMerge`] says
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Inheritance {
+    #[default]
+    FirstFound,
+    DeepMerge(ArrayMerge),
+}
+
+///
+/// `field` of `node` or its ancestors under `inheritance`, `None` when no
+/// node in the chain has it
+///
+pub(crate) fn inherited_field<'a>(ancestors: &Ancestors<'a, '_>, node: &'a Yaml, field: &str, inheritance: Inheritance) -> Result<Option<Yaml>, YamlPathError> {
+    let key = Yaml::String(field.to_string());
+    let arrays = match inheritance {
+        Inheritance::FirstFound => return ancestors.search(node, &|h, _| Ok(h.get(&key).cloned())),
+        Inheritance::DeepMerge(arrays) => arrays,
+    };
+
+    // every value down the chain, nearest first, each hash once however
+    // many paths lead to it
+    let found: RefCell<Vec<(*const Hash, &'a Yaml)>> = RefCell::new(Vec::new());
+    ancestors.search(node, &|h, _| {
+        let mut found = found.borrow_mut();
+        if let Some(value) = h.get(&key) && !found.iter().any(|(seen, _)| std::ptr::eq(*seen, h)) {
+            found.push((h, value));
+        }
+        Ok(None::<()>)
+    })?;
+
+    let mut values = found.into_inner().into_iter().rev().map(|(_, v)| v);
+    let Some(farthest) = values.next() else {
+        return Ok(None);
+    };
+    let mut merged = farthest.clone();
+    for nearer in values {
+        overlay(&mut merged, nearer, arrays);
+    }
+    Ok(Some(merged))
+}
+
+/// merge `top` into `base`, `top` winning wherever both have a value
+fn overlay(base: &mut Yaml, top: &Yaml, arrays: ArrayMerge) {
+    match (base, top) {
+        (Yaml::Hash(b), Yaml::Hash(t)) => {
+            for (k, v) in t {
+                match b.get_mut(k) {
+                    Some(existing) => overlay(existing, v, arrays),
+                    None => { b.insert(k.clone(), v.clone()); }
+                }
+            }
+        }
+        (Yaml::Array(b), Yaml::Array(t)) => match arrays {
+            ArrayMerge::Replace => *b = t.clone(),
+            ArrayMerge::Append => b.extend(t.iter().cloned()),
+            ArrayMerge::ByIndex => {
+                for (i, v) in t.iter().enumerate() {
+                    match b.get_mut(i) {
+                        Some(existing) => overlay(existing, v, arrays),
+                        None => b.push(v.clone()),
+                    }
+                }
+            }
+        },
+        (base, top) => *base = top.clone(),
+    }
+}
@@ -0,0 +1,764 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+use std::str::FromStr;
+use crate::arrayparser::{ArrayParser, BashArrayParser};
+use yaml_rust::Yaml;
+use crate::error::YamlPathError;
+
+///
+/// One step of a [`YamlPath`]
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// member of a hash
+    Key(String),
+    /// element of an array
+    Index(usize),
+    /// `[-n]`, the n'th element counting back from the end of an array
+    NegativeIndex(usize),
+    /// `[start:end]`, the elements from `start` up to but excluding `end`,
+    /// negative bounds count back from the end and missing bounds mean
+    /// the start or end of the array
+    Slice { start: Option<isize>, end: Option<isize> },
+    /// `[?field==value]`, the members or elements satisfying a predicate
+    Filter(Predicate),
+    /// `*`, every member of a hash or element of an array
+    Wildcard,
+    /// `**`, the node itself and every node below it
+    RecursiveDescent,
+}
+
+///
+/// A path into a yaml tree, parsed once into its segments.
+///
+/// Paths are written in a dialect chosen by an [`ArrayParser`]:
+/// `tree.array[0].field` for bash or `tree.array@0.field` for zsh.
+/// `FromStr` and `Display` use the bash dialect, other dialects go
+/// through [`YamlPath::parse_with`] and [`YamlPath::to_string_with`].
+///
+/// # Example
+/// ```rust
+/// use aep_rust_common::arrayparser::ZshArrayParser;
+/// use aep_rust_common::path::{Segment, YamlPath};
+///
+/// let path: YamlPath = "tree.array[0].field".parse().unwrap();
+/// assert_eq!(path.segments()[2], Segment::Index(0));
+/// assert_eq!(path.to_string_with(&ZshArrayParser::new()), "tree.array@0.field");
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct YamlPath {
+    segments: Vec<Segment>,
+}
+
+impl YamlPath {
+    /// the empty path, which addresses the root
+    pub fn new() -> Self {
+        YamlPath { segments: Vec::new() }
+    }
+
+    pub fn parse_with(path: &str, ap: &dyn ArrayParser) -> Result<YamlPath, YamlPathError> {
+        ap.parse_path(path)
+    }
+
+    pub fn to_string_with(&self, ap: &dyn ArrayParser) -> String {
+        ap.format_path(self)
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn push(&mut self, segment: Segment) {
+        self.segments.push(segment);
+    }
+}
+
+impl From<Vec<Segment>> for YamlPath {
+    fn from(segments: Vec<Segment>) -> Self {
+        YamlPath { segments }
+    }
+}
+
+impl FromStr for YamlPath {
+    type Err = YamlPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BashArrayParser::new().parse_path(s)
+    }
+}
+
+impl fmt::Display for YamlPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&BashArrayParser::new().format_path(self))
+    }
+}
+
+/// comparison operator of a [`Predicate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+///
+/// A filter selecting the members or elements whose `field` exists, or
+/// whose `field` compares with a value.  Equality compares numerically when
+/// both sides are numbers, `0x1C` and `28` are equal, otherwise as text.
+/// Ordering comparisons only hold between numbers.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Predicate {
+    pub field: String,
+    pub test: Option<(Comparison, String)>,
+}
+
+/// integers in decimal, hex, octal or binary as well as floats
+fn as_number(s: &str) -> Option<f64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, s),
+    };
+    let radix = |prefix: &str, radix: u32| digits.strip_prefix(prefix).and_then(|d| i64::from_str_radix(d, radix).ok());
+    let value = radix("0x", 16).or_else(|| radix("0o", 8)).or_else(|| radix("0b", 2))
+        .map(|v| v as f64)
+        .or_else(|| digits.parse::<f64>().ok())?;
+    Some(if negative { -value } else { value })
+}
+
+/// the text of a scalar as it would be written in a path
+pub(crate) fn scalar_text(value: &Yaml) -> Option<String> {
+    match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        Yaml::Null => Some(String::from("null")),
+        _ => None,
+    }
+}
+
+impl Predicate {
+    /// test the value of `field`, `None` when the field does not exist
+    pub fn matches(&self, value: Option<&Yaml>) -> bool {
+        let Some(value) = value else { return false };
+        let Some((comparison, expected)) = &self.test else { return true };
+        let Some(actual) = scalar_text(value) else { return *comparison == Comparison::Ne };
+        let numbers = match value {
+            Yaml::Integer(i) => Some(*i as f64),
+            Yaml::Real(r) => r.parse::<f64>().ok(),
+            Yaml::String(s) => as_number(s),
+            _ => None,
+        }.zip(as_number(expected));
+        match (comparison, numbers) {
+            (Comparison::Eq, Some((a, b))) => a == b,
+            (Comparison::Ne, Some((a, b))) => a != b,
+            (Comparison::Eq, None) => actual == *expected,
+            (Comparison::Ne, None) => actual != *expected,
+            (Comparison::Lt, Some((a, b))) => a < b,
+            (Comparison::Le, Some((a, b))) => a <= b,
+            (Comparison::Gt, Some((a, b))) => a > b,
+            (Comparison::Ge, Some((a, b))) => a >= b,
+            _ => false,
+        }
+    }
+}
+
+impl Segment {
+    /// the text between the index brackets for `NegativeIndex` and `Slice`
+    pub(crate) fn selector(&self) -> Option<String> {
+        match self {
+            Segment::NegativeIndex(n) => Some(format!("-{}", n)),
+            Segment::Slice { start, end } => Some(format!("{}:{}",
+                start.map_or(String::new(), |v| v.to_string()),
+                end.map_or(String::new(), |v| v.to_string()))),
+            Segment::Filter(predicate) => {
+                let mut s = format!("?{}", escape_with(&predicate.field, |c| ".@[]\\\"'=!<>".contains(c)));
+                if let Some((comparison, value)) = &predicate.test {
+                    s += comparison.as_str();
+                    if value.is_empty() || value.chars().any(|c| ".@[]\\\"' ".contains(c)) {
+                        s += &format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+                    } else {
+                        s += value;
+                    }
+                }
+                Some(s)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// backslash escape the characters of `s` matching `special`
+fn escape_with(s: &str, special: impl Fn(char) -> bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if special(c) || c.is_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// the absolute index of `[-n]` in an array of `len` elements
+pub(crate) fn negative_index(n: usize, len: usize) -> Option<usize> {
+    if n == 0 || n > len { None } else { Some(len - n) }
+}
+
+/// the indexes selected by `[start:end]` in an array of `len` elements
+pub(crate) fn slice_range(start: Option<isize>, end: Option<isize>, len: usize) -> std::ops::Range<usize> {
+    let clamp = |bound: isize| -> usize {
+        if bound < 0 {
+            len.saturating_sub(bound.unsigned_abs())
+        } else {
+            (bound as usize).min(len)
+        }
+    };
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    start..end.max(start)
+}
+
+///
+/// What remains after the last complete segment of a path that is still
+/// being typed
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartialTail {
+    /// a key prefix, possibly empty
+    Key(String),
+    /// an index has been opened, `[` or `@`, with no digits yet
+    Index,
+}
+
+///
+/// A path as typed on a command line, used to drive completion
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialPath {
+    pub path: YamlPath,
+    pub tail: PartialTail,
+}
+
+///
+/// Anything that can be resolved into a [`YamlPath`] in a given dialect.
+/// Strings are parsed, already compiled paths are borrowed.
+///
+pub trait ToYamlPath {
+    fn to_yaml_path(&self, ap: &dyn ArrayParser) -> Result<std::borrow::Cow<'_, YamlPath>, YamlPathError>;
+
+    /// the text of a path that has not been parsed yet, which may start
+    /// with a document qualifier
+    fn as_text(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl ToYamlPath for str {
+    fn to_yaml_path(&self, ap: &dyn ArrayParser) -> Result<std::borrow::Cow<'_, YamlPath>, YamlPathError> {
+        ap.parse_path(self).map(std::borrow::Cow::Owned)
+    }
+
+    fn as_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl ToYamlPath for String {
+    fn to_yaml_path(&self, ap: &dyn ArrayParser) -> Result<std::borrow::Cow<'_, YamlPath>, YamlPathError> {
+        self.as_str().to_yaml_path(ap)
+    }
+
+    fn as_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl ToYamlPath for YamlPath {
+    fn to_yaml_path(&self, _ap: &dyn ArrayParser) -> Result<std::borrow::Cow<'_, YamlPath>, YamlPathError> {
+        Ok(std::borrow::Cow::Borrowed(self))
+    }
+}
+
+/// a key as read by the tokenizer
+struct KeyToken {
+    text: String,
+    /// part of the key was quoted or escaped, so `*` is not a wildcard
+    quoted: bool,
+    /// the input ended inside a quote or after a backslash
+    open: bool,
+}
+
+///
+/// Tokenizer shared by the '.' separated dialects.  Indexes are opened with
+/// `open` and, if given, closed with `close`.
+///
+pub(crate) struct DottedSyntax {
+    pub open: char,
+    pub close: Option<char>,
+}
+
+impl DottedSyntax {
+    /// characters that may appear in a key without quoting or escaping
+    fn is_key_char(&self, c: char) -> bool {
+        !(c == '.' || c == '[' || c == ']' || c == '\\' || c == '"' || c == '\'' || c == self.open)
+    }
+
+    fn starts_key(&self, c: char) -> bool {
+        self.is_key_char(c) || c == '\\' || c == '"' || c == '\''
+    }
+
+    ///
+    /// Read one key.  A key is a run of plain characters, backslash escapes
+    /// and quoted strings, so `v1\.2`, `"v1.2"` and `v'1.2'` are the same key.
+    /// Inside double quotes a backslash escapes the next character, single
+    /// quotes are taken literally.
+    ///
+    fn read_key(&self, path: &str, chars: &mut Peekable<CharIndices<'_>>, partial: bool) -> Result<KeyToken, YamlPathError> {
+        self.read_token(path, chars, partial, |c| !self.is_key_char(c))
+    }
+
+    /// as `read_key` with plain characters running until `stop` matches
+    fn read_token(&self, path: &str, chars: &mut Peekable<CharIndices<'_>>, partial: bool,
+                  stop: impl Fn(char) -> bool) -> Result<KeyToken, YamlPathError> {
+        let mut key = KeyToken { text: String::new(), quoted: false, open: false };
+        while let Some(&(p, c)) = chars.peek() {
+            if c == '\\' {
+                chars.next();
+                key.quoted = true;
+                match chars.next() {
+                    Some((_, e)) => key.text.push(e),
+                    None if partial => return Ok(KeyToken { open: true, ..key }),
+                    None => return Err(Self::invalid(path, path.len())),
+                }
+            } else if c == '"' || c == '\'' {
+                chars.next();
+                key.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some((_, e)) if e == c => break,
+                        Some((_, '\\')) if c == '"' => match chars.next() {
+                            Some((_, f)) => key.text.push(f),
+                            None if partial => return Ok(KeyToken { open: true, ..key }),
+                            None => return Err(Self::invalid(path, path.len())),
+                        },
+                        Some((_, e)) => key.text.push(e),
+                        None if partial => return Ok(KeyToken { open: true, ..key }),
+                        None => return Err(YamlPathError::InvalidPath {
+                            path: path.to_string(),
+                            segment: c.to_string(),
+                            position: p,
+                        }),
+                    }
+                }
+            } else if !stop(c) {
+                chars.next();
+                key.text.push(c);
+            } else {
+                break;
+            }
+        }
+        Ok(key)
+    }
+
+    /// characters ending a bare predicate value
+    fn ends_value(&self, c: char) -> bool {
+        match self.close {
+            Some(close) => c == close,
+            None => c == '.' || c == self.open,
+        }
+    }
+
+    ///
+    /// Read a filter predicate, `?field`, `field==value`, `?field<3` ...
+    /// The field is a single key, the value runs to the index closer, or to
+    /// the next separator in dialects without one, unless quoted.
+    ///
+    fn read_predicate(&self, path: &str, chars: &mut Peekable<CharIndices<'_>>, partial: bool) -> Result<Option<Predicate>, YamlPathError> {
+        let start = chars.peek().map_or(path.len(), |&(p, _)| p);
+        let questioned = chars.next_if(|&(_, c)| c == '?').is_some();
+        let field = self.read_token(path, chars, partial, |c| !self.is_key_char(c) || "=!<>".contains(c))?;
+        if field.open || (partial && chars.peek().is_none()) {
+            return Ok(None);
+        }
+        if field.text.is_empty() && !field.quoted {
+            return Err(Self::invalid(path, chars.peek().map_or(path.len(), |&(p, _)| p)));
+        }
+
+        let mut op = String::new();
+        while let Some(&(_, c)) = chars.peek() {
+            if !"=!<>".contains(c) {
+                break;
+            }
+            op.push(c);
+            chars.next();
+        }
+        let at = chars.peek().map_or(path.len(), |&(p, _)| p);
+        let comparison = match op.as_str() {
+            // an existence test needs the '?' to tell it from a typo'd index
+            "" if questioned => return Ok(Some(Predicate { field: field.text, test: None })),
+            "" => return Err(Self::invalid(path, start)),
+            "=" | "==" => Comparison::Eq,
+            "!=" => Comparison::Ne,
+            "<" => Comparison::Lt,
+            "<=" => Comparison::Le,
+            ">" => Comparison::Gt,
+            ">=" => Comparison::Ge,
+            _ => return Err(YamlPathError::InvalidPath { path: path.to_string(), segment: op.clone(), position: at - op.len() }),
+        };
+        let value = self.read_token(path, chars, partial, |c| self.ends_value(c))?;
+        if value.open || (partial && chars.peek().is_none()) {
+            return Ok(None);
+        }
+        Ok(Some(Predicate { field: field.text, test: Some((comparison, value.text)) }))
+    }
+
+    ///
+    /// Read what follows an index opener: `*`, `n`, `-n`, a slice `a:b`
+    /// where either bound may be omitted or negative, or a filter predicate,
+    /// then the closer if the
+    /// dialect has one.  `None` is returned when `partial` allowed the input
+    /// to end before anything selecting an element was typed.
+    ///
+    fn read_index(&self, path: &str, chars: &mut Peekable<CharIndices<'_>>, partial: bool) -> Result<Option<Segment>, YamlPathError> {
+        let segment = if let Some(&(_, '*')) = chars.peek() {
+            chars.next();
+            Segment::Wildcard
+        } else if chars.peek().is_some_and(|&(_, c)| !(c.is_ascii_digit() || c == '-' || c == ':' || Some(c) == self.close)) {
+            match self.read_predicate(path, chars, partial)? {
+                Some(predicate) => Segment::Filter(predicate),
+                None => return Ok(None),
+            }
+        } else {
+            let first = Self::read_int(path, chars)?;
+            if let Some(&(_, ':')) = chars.peek() {
+                chars.next();
+                let second = Self::read_int(path, chars)?;
+                Segment::Slice {
+                    start: first.map(|t| Self::to_isize(path, t)).transpose()?,
+                    end: second.map(|t| Self::to_isize(path, t)).transpose()?,
+                }
+            } else {
+                match first {
+                    None if partial && chars.peek().is_none() => return Ok(None),
+                    None => return Err(Self::invalid(path, chars.peek().map_or(path.len(), |&(p, _)| p))),
+                    Some(text) => match text.strip_prefix('-') {
+                        Some(digits) if digits.trim_start_matches('0').is_empty() => Segment::Index(0),
+                        Some(digits) => Segment::NegativeIndex(Self::to_usize(path, text, digits)?),
+                        None => Segment::Index(Self::to_usize(path, text, text)?),
+                    },
+                }
+            }
+        };
+        if let Some(close) = self.close {
+            match chars.peek() {
+                Some(&(_, d)) if d == close => { chars.next(); }
+                None if partial => {}
+                Some(&(p, _)) => return Err(Self::invalid(path, p)),
+                None => return Err(Self::invalid(path, path.len())),
+            }
+        }
+        Ok(Some(segment))
+    }
+
+    /// an optionally negative run of digits, `None` if there are no digits
+    fn read_int<'p>(path: &'p str, chars: &mut Peekable<CharIndices<'_>>) -> Result<Option<&'p str>, YamlPathError> {
+        let start = match chars.peek() {
+            Some(&(p, _)) => p,
+            None => return Ok(None),
+        };
+        let mut end = start;
+        if let Some(&(p, '-')) = chars.peek() {
+            chars.next();
+            end = p + 1;
+        }
+        while let Some(&(p, d)) = chars.peek() {
+            if !d.is_ascii_digit() {
+                break;
+            }
+            end = p + 1;
+            chars.next();
+        }
+        match &path[start..end] {
+            "" => Ok(None),
+            "-" if chars.peek().is_none() => Ok(None),
+            "-" => Err(Self::invalid(path, end)),
+            text => Ok(Some(text)),
+        }
+    }
+
+    fn to_usize(path: &str, text: &str, digits: &str) -> Result<usize, YamlPathError> {
+        digits.parse::<usize>().map_err(|_| YamlPathError::IndexOutOfBounds {
+            path: path.to_string(),
+            segment: text.to_string(),
+        })
+    }
+
+    fn to_isize(path: &str, text: &str) -> Result<isize, YamlPathError> {
+        text.parse::<isize>().map_err(|_| YamlPathError::IndexOutOfBounds {
+            path: path.to_string(),
+            segment: text.to_string(),
+        })
+    }
+
+    ///
+    /// Escape `key` so that `tokenize` reads it back as a single key.
+    /// Whitespace is escaped too.  These are path escapes, a shell needs
+    /// the result quoted again, as `shell/aep-completion.bash` does.
+    ///
+    pub fn escape_key(&self, key: &str) -> String {
+        self.escape_key_with(key, |_| false)
+    }
+
+    /// [`escape_key`](Self::escape_key) also escaping the characters
+    /// `special` picks out, which a shell would otherwise expand
+    pub fn escape_key_with(&self, key: &str, special: impl Fn(char) -> bool) -> String {
+        if key.is_empty() {
+            return String::from("\"\"");
+        }
+        if key == "*" || key == "**" {
+            return format!("\\{}", key);
+        }
+        escape_with(key, |c| !self.is_key_char(c) || special(c))
+    }
+
+    pub(crate) fn invalid(path: &str, position: usize) -> YamlPathError {
+        let segment = path[position..].chars().next().map(String::from).unwrap_or_default();
+        YamlPathError::InvalidPath { path: path.to_string(), segment, position }
+    }
+
+    ///
+    /// Split `path` into segments.  With `partial` set, a trailing separator,
+    /// an unterminated index or a key still being typed are returned as the
+    /// tail rather than treated as errors.
+    ///
+    pub fn tokenize(&self, path: &str, partial: bool) -> Result<PartialPath, YamlPathError> {
+        let bytes_len = path.len();
+        let mut chars = path.char_indices().peekable();
+        let mut segments = Vec::new();
+        // true when a '.' has been consumed and a key must follow
+        let mut need_key = false;
+
+        loop {
+            let (pos, c) = match chars.peek() {
+                Some(&(p, c)) => (p, c),
+                None => {
+                    if need_key && !partial {
+                        return Err(Self::invalid(path, bytes_len));
+                    }
+                    return Ok(PartialPath { path: YamlPath::from(segments), tail: PartialTail::Key(String::new()) });
+                }
+            };
+
+            if c == self.open && !need_key {
+                chars.next();
+                let segment = match self.read_index(path, &mut chars, partial)? {
+                    Some(segment) => segment,
+                    None => return Ok(PartialPath { path: YamlPath::from(segments), tail: PartialTail::Index }),
+                };
+                segments.push(segment);
+                match chars.peek() {
+                    Some(&(_, '.')) => { chars.next(); need_key = true; }
+                    Some(&(_, d)) if d == self.open => {}
+                    Some(&(p, _)) => return Err(Self::invalid(path, p)),
+                    None => {}
+                }
+                continue;
+            }
+
+            if !self.starts_key(c) {
+                return Err(Self::invalid(path, pos));
+            }
+
+            let key = self.read_key(path, &mut chars, partial)?;
+            if key.open || (partial && chars.peek().is_none()) {
+                return Ok(PartialPath { path: YamlPath::from(segments), tail: PartialTail::Key(key.text) });
+            }
+            let segment = match (key.quoted, key.text.as_str()) {
+                (false, "*") => Segment::Wildcard,
+                (false, "**") => Segment::RecursiveDescent,
+                _ => Segment::Key(key.text),
+            };
+            need_key = false;
+            match chars.peek() {
+                None => segments.push(segment),
+                Some(&(_, '.')) => {
+                    chars.next();
+                    segments.push(segment);
+                    need_key = true;
+                }
+                Some(&(_, d)) if d == self.open => segments.push(segment),
+                Some(&(p, _)) => return Err(Self::invalid(path, p)),
+            }
+        }
+    }
+
+    pub fn format_index(&self, index: usize) -> String {
+        self.format_selector(&index.to_string())
+    }
+
+    pub fn format_selector(&self, selector: &str) -> String {
+        match self.close {
+            Some(close) => format!("{}{}{}", self.open, selector, close),
+            None => format!("{}{}", self.open, selector),
+        }
+    }
+}
+
+///
+/// Split an RFC 6901 JSON Pointer, `/GPIO/pins/0/set`, into segments.
+/// `~0` and `~1` stand for `~` and `/`.  Tokens that are valid array
+/// indexes become [`Segment::Index`], which also names a hash member whose
+/// key is that number.  With `partial` set the last token is returned as
+/// the tail.
+///
+pub(crate) fn tokenize_pointer(path: &str, partial: bool) -> Result<PartialPath, YamlPathError> {
+    if path.is_empty() {
+        return Ok(PartialPath { path: YamlPath::new(), tail: PartialTail::Key(String::new()) });
+    }
+    if !path.starts_with('/') {
+        return Err(DottedSyntax::invalid(path, 0));
+    }
+
+    let mut segments = Vec::new();
+    let mut position = 1;
+    let tokens: Vec<&str> = path[1..].split('/').collect();
+    for (i, raw) in tokens.iter().enumerate() {
+        let mut token = String::with_capacity(raw.len());
+        let mut chars = raw.char_indices().peekable();
+        while let Some((p, c)) = chars.next() {
+            if c != '~' {
+                token.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some((_, '0')) => token.push('~'),
+                Some((_, '1')) => token.push('/'),
+                None if partial && i + 1 == tokens.len() => break,
+                _ => return Err(DottedSyntax::invalid(path, position + p)),
+            }
+        }
+
+        if partial && i + 1 == tokens.len() {
+            return Ok(PartialPath { path: YamlPath::from(segments), tail: PartialTail::Key(token) });
+        }
+        let is_index = *raw == "0" || (!raw.is_empty() && !raw.starts_with('0') && raw.bytes().all(|b| b.is_ascii_digit()));
+        segments.push(match is_index {
+            true => Segment::Index(raw.parse::<usize>().map_err(|_| YamlPathError::IndexOutOfBounds {
+                path: path.to_string(),
+                segment: raw.to_string(),
+            })?),
+            false => Segment::Key(token),
+        });
+        position += raw.len() + 1;
+    }
+    Ok(PartialPath { path: YamlPath::from(segments), tail: PartialTail::Key(String::new()) })
+}
+
+/// escape a JSON Pointer reference token
+pub(crate) fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+///
+/// A parsed `parent:` reference.
+///
+/// - `a.b`: a path from the root
+/// - `$.a.b` or `$`: explicitly from the root
+/// - `.a`: a sibling of the node holding the reference, each further dot
+///   climbs one level, so `..a` is a member of the grandparent and
+///   `...a.b` one of the level above that
+///
+/// References starting with `/` are JSON Pointers, `./a` and `$/a` mix the
+/// two.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParentReference {
+    Absolute(YamlPath),
+    Relative { up: usize, path: YamlPath },
+}
+
+impl ParentReference {
+    pub(crate) fn parse(reference: &str, ap: &dyn ArrayParser) -> Result<Self, YamlPathError> {
+        // the path after the marker, with error positions in `reference`
+        let rest = |offset: usize| ap.parse_reference(&reference[offset..]).map_err(|e| match e {
+            YamlPathError::InvalidPath { segment, position, .. } => {
+                YamlPathError::InvalidPath { path: reference.to_string(), segment, position: position + offset }
+            }
+            e => e,
+        });
+        if let Some(after) = reference.strip_prefix('$') {
+            let offset = if after.starts_with('.') { 2 } else { 1 };
+            return Ok(ParentReference::Absolute(rest(offset)?));
+        }
+        let up = reference.len() - reference.trim_start_matches('.').len();
+        if up == 0 {
+            Ok(ParentReference::Absolute(rest(0)?))
+        } else {
+            Ok(ParentReference::Relative { up, path: rest(up)? })
+        }
+    }
+
+    ///
+    /// The path from the root this reference names when it is held by the
+    /// node at `from`.  `None` when a relative reference climbs above the
+    /// root or `from` is unknown.
+    ///
+    pub(crate) fn resolve(&self, from: Option<&YamlPath>) -> Option<YamlPath> {
+        match self {
+            ParentReference::Absolute(path) => Some(path.clone()),
+            ParentReference::Relative { up, path } => {
+                let from = from?.segments();
+                let kept = from.len().checked_sub(*up)?;
+                let mut segments = from[..kept].to_vec();
+                segments.extend_from_slice(path.segments());
+                Some(YamlPath::from(segments))
+            }
+        }
+    }
+}
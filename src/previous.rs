@@ -0,0 +1,183 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::io::Write;
+use std::path::Path;
+use yaml_rust::Yaml;
+use crate::completion::Completion;
+use crate::error::YamlPathError;
+use crate::matcher::rank;
+use crate::yaml_descender::YamlDescender;
+
+///
+/// What to complete after a command-line word, from
+/// `completion-metadata.previous`
+///
+/// ```yaml
+/// completion-metadata:
+///   previous:
+///     "-f": {action: reload}                   # a config file to load
+///     "-o": {action: files}
+///     "-C": {action: directories}
+///     "--mode": {action: enum, values: [fast, slow]}
+///     "--pins": {action: root, root: GPIO.pins} # paths below GPIO.pins
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum PreviousAction {
+    /// file and directory names
+    Files,
+    /// directory names only
+    Directories,
+    /// one of a fixed list of words
+    Enum(Vec<String>),
+    /// paths below another node, in the descender's dialect
+    Root(String),
+    /// file names, the word naming a file the caller loads instead
+    Reload,
+}
+
+impl PreviousAction {
+    ///
+    /// Read the action for `word`, errors naming the offending key below
+    /// `completion-metadata.previous`
+    ///
+    pub fn from_yaml(word: &str, yaml: &Yaml) -> Result<PreviousAction, YamlPathError> {
+        let path = format!("completion-metadata.previous.{}", word);
+        let mismatch = |segment: &str, expected: &'static str| YamlPathError::TypeMismatch { path: path.clone(), segment: segment.to_string(), expected };
+        if yaml.as_hash().is_none() {
+            return Err(YamlPathError::TypeMismatch { path: String::from("completion-metadata.previous"), segment: word.to_string(), expected: "a hash" });
+        }
+        let action = yaml["action"].as_str().ok_or_else(|| mismatch("action", "a string"))?;
+        match action {
+            "files" => Ok(PreviousAction::Files),
+            "directories" => Ok(PreviousAction::Directories),
+            "reload" => Ok(PreviousAction::Reload),
+            "enum" => yaml["values"].as_vec()
+                .and_then(|v| v.iter().map(|s| s.as_str().map(str::to_string)).collect::<Option<Vec<String>>>())
+                .map(PreviousAction::Enum)
+                .ok_or_else(|| mismatch("values", "a list of strings")),
+            "root" => yaml["root"].as_str()
+                .map(|r| PreviousAction::Root(r.to_string()))
+                .ok_or_else(|| mismatch("root", "a string")),
+            _ => Err(mismatch("action", "one of files, directories, enum, root or reload")),
+        }
+    }
+}
+
+///
+/// The files, or only the directories, whose names start with `prefix`,
+/// directories ending in `/`.  Hidden files are offered once `prefix`
+/// names them with a leading dot.
+///
+pub(crate) fn file_candidates(prefix: &str, directories_only: bool) -> Vec<String> {
+    let (dir, start) = match prefix.rfind('/') {
+        Some(i) => (&prefix[..=i], &prefix[i + 1..]),
+        None => ("", prefix)
+    } ;
+    let entries = match std::fs::read_dir(if dir.is_empty() { Path::new(".") } else { Path::new(dir) }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new()
+    } ;
+    let mut candidates: Vec<String> = entries.filter_map(Result::ok).filter_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(start) || (name.starts_with('.') && !start.starts_with('.')) {
+            return None;
+        }
+        let is_dir = entry.path().is_dir();
+        if is_dir {
+            Some(format!("{}{}/", dir, name))
+        } else if directories_only {
+            None
+        } else {
+            Some(format!("{}{}", dir, name))
+        }
+    }).collect();
+    candidates.sort();
+    candidates
+}
+
+impl YamlDescender {
+    /// the action `completion-metadata.previous` gives for `word`
+    pub fn previous_action(&self, word: &str) -> Option<&PreviousAction> {
+        self.metadata.previous.get(word)
+    }
+
+    ///
+    /// Complete `current`, the word being typed, given the word before it
+    /// on the command line.  Without an action for `previous`, a
+    /// `previous` naming a node with a [`ValueHint`](crate::hints::ValueHint)
+    /// completes its value, otherwise this is
+    /// [`write_completions`](crate::descender::Descender::write_completions).
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// completion-metadata:
+    ///   previous:
+    ///     --mode: {action: enum, values: [fast, slow, safe]}
+    ///     --pin: {action: root, root: GPIO.pins}
+    /// GPIO:
+    ///   pins: {pin0: {bits: 1}, pin1: {bits: 2}}
+    /// " ;
+    /// let descender = YamlDescender::new(s, true).unwrap() ;
+    /// let mut out = Vec::new() ;
+    /// descender.write_completions_after(&mut out, "--mode", "s", false).unwrap() ;
+    /// assert_eq!(String::from_utf8(out).unwrap(), "safe\nslow\n") ;
+    /// let mut out = Vec::new() ;
+    /// descender.write_completions_after(&mut out, "--pin", "pin1.", false).unwrap() ;
+    /// assert_eq!(String::from_utf8(out).unwrap(), "pin1.bits\n") ;
+    /// ```
+    ///
+    pub fn write_completions_after(&self, writer: &mut dyn Write, previous: &str, current: &str, add_descriptions: bool) -> std::io::Result<()> {
+        let add_descriptions = add_descriptions || self.metadata.get_descriptions ;
+        self.render(writer, self.collect_completions_after(previous, current, add_descriptions), add_descriptions)
+    }
+
+    /// [`write_completions_after`](Self::write_completions_after) as
+    /// [`Completion`]s, with descriptions
+    pub fn completions_after(&self, previous: &str, current: &str) -> Vec<Completion> {
+        self.collect_completions_after(previous, current, true).unwrap_or_default()
+    }
+
+    fn collect_completions_after(&self, previous: &str, current: &str, add_descriptions: bool) -> Result<Vec<Completion>, YamlPathError> {
+        let candidates = match self.previous_action(previous) {
+            None => return Ok(match self.hinted(previous, current, "") {
+                Some(hinted) => hinted,
+                None => self.collect_completions(current, add_descriptions)?
+            }),
+            Some(PreviousAction::Files) | Some(PreviousAction::Reload) => file_candidates(current, false),
+            Some(PreviousAction::Directories) => file_candidates(current, true),
+            Some(PreviousAction::Enum(values)) => rank(values.clone(), String::as_str, current, &self.matchers),
+            Some(PreviousAction::Root(root)) => {
+                let below = self.ap.parse_reference(root)?;
+                return match self.ap.parse_partial(current) {
+                    Ok(partial) => self.complete(self.document(), &below, "", &partial, add_descriptions),
+                    Err(_) => Ok(Vec::new())
+                } ;
+            }
+        } ;
+        Ok(candidates.into_iter().map(Completion::word).collect())
+    }
+}
@@ -0,0 +1,81 @@
+// 
+// SPDX-License-Identifier: MIT
+// 
+// Copyright (c) 2025 Andrew Ellis Page
+// 
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+// 
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+// 
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+// 
+use std::fmt;
+use yaml_rust::Yaml;
+use crate::path::scalar_text;
+
+///
+/// Where an inherited value came from, see
+/// [`YamlDescender::get_field_with_provenance`](crate::yaml_descender::YamlDescender::get_field_with_provenance)
+///
+/// - `path`: the node the field was asked of
+/// - `field`: the field asked for
+/// - `value`: the value found
+/// - `visited`: every node searched, in order, ending with `found_at`
+/// - `found_at`: the node holding the field
+///
+/// Paths are in the dialect of the descender that produced them.
+/// `Display` renders an explanation, one searched node per line.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    pub path: String,
+    pub field: String,
+    pub value: Yaml,
+    pub visited: Vec<String>,
+    pub found_at: String,
+}
+
+impl Provenance {
+    /// whether the value was inherited rather than set on `path` itself
+    pub fn is_inherited(&self) -> bool {
+        self.visited.len() > 1
+    }
+
+    /// the explanation `Display` renders
+    pub fn explain(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = scalar_text(&self.value).unwrap_or_else(|| match self.value {
+            Yaml::Hash(_) => String::from("(a hash)"),
+            Yaml::Array(_) => String::from("(an array)"),
+            _ => String::from("(no value)"),
+        });
+        writeln!(f, "{} of {} is {}", self.field, display_path(&self.path), value)?;
+        for (i, visited) in self.visited.iter().enumerate() {
+            let outcome = if i + 1 == self.visited.len() { "found" } else { "not set" };
+            writeln!(f, "  {}: {}", display_path(visited), outcome)?;
+        }
+        Ok(())
+    }
+}
+
+/// the empty path is the root
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "the root" } else { path }
+}
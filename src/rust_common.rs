@@ -24,9 +24,10 @@
 use yaml_rust::Yaml;
 use yaml_rust::yaml::Hash;
 use std::collections::HashSet;
+use crate::matcher::{rank, Matcher};
 
 
-pub fn sep(y: &Yaml, empty_path: bool) -> &str {
+pub fn sep(y: &Yaml, empty_path: bool) -> &'static str {
     if empty_path { return "" ; }
     match y {
         Yaml::Hash(_) => { "." }
@@ -35,19 +36,8 @@ pub fn sep(y: &Yaml, empty_path: bool) -> &str {
     }
 }
 
-pub fn keys_starting_with<'a>(prefix : &str, map : &'a Hash, ignores: &HashSet<String>) -> Vec<&'a Yaml> {
-    let mut keys = Vec::new();
-
-    for (key, _) in map {
-        if let Some(key_str) = key.as_str() {
-            if ignores.contains(&key_str.to_string()) {
-                continue ;
-            }
-            if key_str.starts_with(prefix) {
-                keys.push(key);
-            }
-        }
-    }
-    keys.sort();
-    keys
-}
\ No newline at end of file
+/// the keys of `map` not in `ignores` that `matchers` match against `pattern`, see [`rank`]
+pub fn matching_keys<'a>(pattern : &str, map : &'a Hash, ignores: &HashSet<String>, matchers: &[Box<dyn Matcher>]) -> Vec<&'a Yaml> {
+    let keys = map.keys().filter(|key| key.as_str().is_some_and(|k| !ignores.contains(k))).collect();
+    rank(keys, |key| key.as_str().unwrap(), pattern, matchers)
+}
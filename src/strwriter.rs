@@ -34,7 +34,7 @@ impl StrWriter {
         StrWriter { writer: BufWriter::new(Vec::new()) }
     }
 
-    pub fn to_string(self) -> std::io::Result<String> {
+    pub fn into_string(self) -> std::io::Result<String> {
         let vec = self.writer.into_inner()?;
         String::from_utf8(vec).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
     }
@@ -32,8 +32,22 @@ field2: foo
 
 mod u_tests {
     use crate::yaml_path::yaml_path_field;
+    use crate::yaml_path::{parent_cycles, yaml_field_parent, yaml_field_parent_with};
+    use crate::merge::{ArrayMerge, Inheritance};
+    use crate::include::load_file;
+    use crate::get_metadata::Metadata;
+    use crate::previous::PreviousAction;
+    use crate::hints::{split_value, ValueHint};
+    use crate::matcher::{IgnoreCasePrefixMatcher, MatchMode, Matcher, PrefixMatcher, SubsequenceMatcher, SubstringMatcher};
+    use crate::completion::{escape, fish_script, unescape, Completion, NodeKind, BASH_FUNCTION, PROTOCOL_HEADER, ZSH_FUNCTION};
+    use crate::flatten::{flatten, FlattenOptions};
     use crate::yaml_path::yaml_path;
+    use crate::yaml_path::yaml_query;
+    use crate::yaml_path::{remove_at_path, set_at_path, yaml_path_mut, yaml_path_of};
     use crate::descender::Descender;
+    use crate::error::YamlPathError;
+    use crate::arrayparser::{ArrayParser, Dialect, ZshArrayParser};
+    use crate::path::{PartialPath, PartialTail, Segment, YamlPath};
     use crate::find_config_file::find_config_file;
     use crate::strwriter::StrWriter;
     use crate::unittests::SOURCE1;
@@ -68,8 +82,8 @@ mod u_tests {
 
     fn input_output_check(d: &YamlDescender, input: &str, output: &str) {
         let mut result_buffer = StrWriter::new() ;
-        d.write_completions(&mut result_buffer, &input, false).expect("write failed") ;
-        let result_str = result_buffer.to_string().expect("write failed") ;
+        d.write_completions(&mut result_buffer, input, false).expect("write failed") ;
+        let result_str = result_buffer.into_string().expect("write failed") ;
         assert_eq!(result_str, output);
     }
 
@@ -98,7 +112,6 @@ mod u_tests {
         input_output_check(&ZshDescender, "array", "array@0\narray@1\narray@2\n") ;
     }
     #[test]
-    #[ignore]
     fn test_array2() {
         input_output_check(&BashDescender, "array[2]", "array[2][0]\narray[2][1]\narray[2][2]\n");
     }
@@ -139,8 +152,6 @@ mod u_tests {
     fn test_descending() {
 
         input_output_check(&BashDescender, "ulev", "ulevel.level1.level2.level3\n") ;
-
-        return ;
     }
 
 
@@ -181,25 +192,1013 @@ mod u_tests {
     #[should_panic(expected = "number in root.array.number is not a hash")]
     fn test_badvalue_hash() {
         let yaml =  &YamlData ;
-        let _i = yaml_scalar!(yaml, "root.array.number", i64).unwrap();
+        let _i = yaml_scalar!(yaml, "root.array.number", i64).unwrap_or_else(|e| panic!("{}", e));
     }
     #[test]
     #[should_panic(expected = "root.array[0].number[1] 1 is not an array")]
     fn test_badvalue_array() {
         let yaml = &YamlData ;
-        let _i = yaml_scalar!(yaml, "root.array[0].number[1]", i64).unwrap();
+        let _i = yaml_scalar!(yaml, "root.array[0].number[1]", i64).unwrap_or_else(|e| panic!("{}", e));
     }
 
     #[test]
     #[should_panic(expected = "numberX not found in root.array[0].numberX")]
     fn test_nosuch_member() {
-        let _i = yaml_scalar!(&YamlData, "root.array[0].numberX", i64).unwrap();
+        let _i = yaml_scalar!(&YamlData, "root.array[0].numberX", i64).unwrap_or_else(|e| panic!("{}", e));
     }
 
     #[test]
     #[should_panic(expected = "100 is out of bounds in root.array[100].number")]
     fn test_nosuch_index() {
-        let _i = yaml_scalar!(&YamlData, "root.array[100].number", i64).unwrap();
+        let _i = yaml_scalar!(&YamlData, "root.array[100].number", i64).unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let e = yaml_scalar!(&YamlData, "root.array[0].numberX", i64).unwrap_err();
+        assert_eq!(e, YamlPathError::KeyNotFound { path: "root.array[0].numberX".to_string(), segment: "numberX".to_string() });
+        let e = yaml_scalar!(&YamlData, "root.array[0].string", i64).unwrap_err();
+        assert!(matches!(e, YamlPathError::TypeMismatch { expected: "an integer", .. }));
+        assert_eq!(e.path(), "root.array[0].string");
+
+        let doccer = YamlDescender::new(TEST_SOURCE, true).unwrap();
+        let e = doccer.get_string_field_or_parent("root.array[0]", "number").unwrap_err();
+        assert_eq!(e.to_string(), "root.array[0].number is not a string");
+        let e = doccer.get_int_field_or_parent("root.array[5]", "number").unwrap_err();
+        assert!(matches!(e, YamlPathError::IndexOutOfBounds { .. }));
+        assert_eq!(e.segment(), "5");
+
+        assert!(matches!(YamlDescender::new("a: [", true), Err(YamlPathError::ParseError { .. })));
+    }
+
+    #[test]
+    fn test_yaml_path_parse() {
+        let path: YamlPath = "GPIO.pins[1].set".parse().unwrap();
+        assert_eq!(path.segments(), &[Segment::Key("GPIO".to_string()), Segment::Key("pins".to_string()),
+            Segment::Index(1), Segment::Key("set".to_string())]);
+        assert_eq!(path.to_string(), "GPIO.pins[1].set");
+
+        let zsh = ZshArrayParser::new();
+        let zpath = YamlPath::parse_with("GPIO.pins@1.set", &zsh).unwrap();
+        assert_eq!(zpath, path);
+        assert_eq!(path.to_string_with(&zsh), "GPIO.pins@1.set");
+        assert_eq!("array[2][0]".parse::<YamlPath>().unwrap().to_string_with(&zsh), "array@2@0");
+
+        assert_eq!(yaml_path(&YamlData, &path.to_string()), yaml_path(&YamlData, &path));
+    }
+
+    #[test]
+    fn test_yaml_path_parse_errors() {
+        let e = "root..array".parse::<YamlPath>().unwrap_err();
+        assert_eq!(e, YamlPathError::InvalidPath { path: "root..array".to_string(), segment: ".".to_string(), position: 5 });
+        let e = "root.array[x]".parse::<YamlPath>().unwrap_err();
+        assert!(matches!(e, YamlPathError::InvalidPath { position: 11, .. }));
+        let e = "root.array[0".parse::<YamlPath>().unwrap_err();
+        assert!(matches!(e, YamlPathError::InvalidPath { position: 12, .. }));
+        let e = "root.".parse::<YamlPath>().unwrap_err();
+        assert_eq!(e.to_string(), "root. is not a valid path: unexpected end at 5");
+        let e = "root.array[99999999999999999999999]".parse::<YamlPath>().unwrap_err();
+        assert!(matches!(e, YamlPathError::IndexOutOfBounds { .. }));
+    }
+
+    #[test]
+    fn test_partial_path() {
+        let zsh = ZshArrayParser::new();
+        assert_eq!(zsh.parse_partial("GPIO.pins@").unwrap(), PartialPath {
+            path: YamlPath::from(vec![Segment::Key("GPIO".to_string()), Segment::Key("pins".to_string())]),
+            tail: PartialTail::Index
+        });
+        assert_eq!(zsh.parse_partial("GPIO.p").unwrap().tail, PartialTail::Key("p".to_string()));
+        assert_eq!(zsh.parse_partial("GPIO.").unwrap().tail, PartialTail::Key("".to_string()));
+    }
+
+    #[test]
+    fn test_path_completions() {
+        let path: YamlPath = "GPIO.pins[0]".parse().unwrap();
+        let mut result_buffer = StrWriter::new() ;
+        BashDescender.write_path_completions(&mut result_buffer, &path, false).expect("write failed") ;
+        assert_eq!(result_buffer.into_string().unwrap(), "GPIO.pins[0].clear\nGPIO.pins[0].function\nGPIO.pins[0].level\nGPIO.pins[0].set\n");
+        assert_eq!(BashDescender.yaml_descend_path(&path).unwrap(), BashDescender.yaml_descend_path("GPIO.pins[0]").unwrap());
+    }
+
+    const QUOTED_SOURCE: &str = r#"---
+registers:
+    "v1.2":
+        offset: 4
+    "ctrl[alt]":
+        offset: 8
+    "my key": 12
+    plain: 16
+"# ;
+
+    #[test]
+    fn test_quoted_keys() {
+        let d = YamlDescender::new(QUOTED_SOURCE, true).unwrap();
+        assert_eq!(d.yaml_descend_path(r#"registers."v1.2".offset"#).unwrap(), &Yaml::Integer(4));
+        assert_eq!(d.yaml_descend_path(r"registers.v1\.2.offset").unwrap(), &Yaml::Integer(4));
+        assert_eq!(d.yaml_descend_path("registers.'ctrl[alt]'.offset").unwrap(), &Yaml::Integer(8));
+        assert_eq!(d.yaml_descend_path(r#"registers."my key""#).unwrap(), &Yaml::Integer(12));
+
+        let path: YamlPath = r"registers.ctrl\[alt\]".parse().unwrap();
+        assert_eq!(path.segments()[1], Segment::Key("ctrl[alt]".to_string()));
+        assert_eq!(path.to_string().parse::<YamlPath>().unwrap(), path);
+
+        let e = r#"registers."v1.2"#.parse::<YamlPath>().unwrap_err();
+        assert!(matches!(e, YamlPathError::InvalidPath { position: 10, .. }));
+    }
+
+    #[test]
+    fn test_quoted_completions() {
+        let d = YamlDescender::new(QUOTED_SOURCE, true).unwrap();
+        input_output_check(&d, "registers.", "registers.ctrl\\[alt\\]\nregisters.my\\ key\nregisters.plain\nregisters.v1\\.2\n");
+        input_output_check(&d, "registers.\"v1.", "registers.v1\\.2.offset\n");
+        input_output_check(&d, "registers.ctrl\\[", "registers.ctrl\\[alt\\].offset\n");
+        // what is emitted names the node it was completed from
+        for completion in d.completions("registers.") {
+            let key = completion.display.as_str();
+            let node = d.yaml_descend_path(completion.text.as_str()).unwrap();
+            assert!(std::ptr::eq(node, &d.yaml_descend_path("registers").unwrap()[key]), "{}", completion.text);
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        let offsets = BashDescender.yaml_query("GPIO.pins[*].*.offset").unwrap();
+        assert_eq!(offsets.len(), 8);
+        assert_eq!(offsets[0].0.to_string(), "GPIO.pins[0].function.offset");
+        assert_eq!(offsets[7].0.to_string(), "GPIO.pins[1].level.offset");
+
+        let everywhere = BashDescender.yaml_query("**.offset").unwrap();
+        assert_eq!(everywhere.len(), 10);
+        assert!(everywhere.iter().any(|(p, _)| p.to_string() == "GPIO.words.function1.offset"));
+
+        let zsh_levels = ZshDescender.yaml_query("GPIO.pins@*.level").unwrap();
+        assert_eq!(ZshDescender.format_path(&zsh_levels[1].0), "GPIO.pins@1.level");
+
+        assert!(BashDescender.yaml_query("GPIO.nothing.*").unwrap().is_empty());
+        let e = BashDescender.yaml_descend_path("GPIO.pins[*]").unwrap_err();
+        assert!(matches!(e, YamlPathError::InvalidPath { position: 10, .. }));
+        assert_eq!(r"a.\*".parse::<YamlPath>().unwrap().segments()[1], Segment::Key("*".to_string()));
+    }
+
+    #[test]
+    fn test_negative_index_and_slices() {
+        assert_eq!(yaml_path(&YamlData, "root.array[-1].number"), Ok(Yaml::Integer(4)));
+        let e = yaml_path(&YamlData, "root.array[-2]").unwrap_err();
+        assert_eq!(e, YamlPathError::IndexOutOfBounds { path: "root.array[-2]".to_string(), segment: "-2".to_string() });
+        assert_eq!(ZshDescender.yaml_descend_path("array@-1@0").unwrap(), &Yaml::String("one".to_string()));
+
+        let path: YamlPath = "array[1:][:-1]".parse().unwrap();
+        assert_eq!(path.segments()[1], Segment::Slice { start: Some(1), end: None });
+        assert_eq!(path.segments()[2], Segment::Slice { start: None, end: Some(-1) });
+        assert_eq!(path.to_string_with(&ZshArrayParser::new()), "array@1:@:-1");
+
+        let matches = BashDescender.yaml_query("array[1:]").unwrap();
+        assert_eq!(matches.iter().map(|(p, _)| p.to_string()).collect::<Vec<_>>(), vec!["array[1]", "array[2]"]);
+        let matches = ZshDescender.yaml_query("array@-1@-2:").unwrap();
+        assert_eq!(ZshDescender.format_path(&matches[0].0), "array@2@1");
+        assert_eq!(matches.len(), 2);
+
+        input_output_check(&BashDescender, "array[0:2]", "array[0]\narray[1]\n");
+        input_output_check(&BashDescender, "array[-1]", "array[2][0]\narray[2][1]\narray[2][2]\n");
+        input_output_check(&ZshDescender, "GPIO.pins@-1.s", "GPIO.pins@1.set.bits\nGPIO.pins@1.set.offset\nGPIO.pins@1.set.read-write\n");
+        input_output_check(&BashDescender, "GPIO.pins[:].l", "GPIO.pins[0].level.bits\nGPIO.pins[0].level.offset\nGPIO.pins[0].level.read-write\nGPIO.pins[1].level.bits\nGPIO.pins[1].level.offset\nGPIO.pins[1].level.read-write\n");
+
+        let e = "array[99999999999999999999]".parse::<YamlPath>().unwrap_err();
+        assert!(matches!(e, YamlPathError::IndexOutOfBounds { .. }));
+        let e = "array[-99999999999999999999:]".parse::<YamlPath>().unwrap_err();
+        assert!(matches!(e, YamlPathError::IndexOutOfBounds { .. }));
+        assert!(yaml_path(&YamlData, "root.array[99999999999999999999]").is_err());
+    }
+
+    #[test]
+    fn test_filter_predicates() {
+        let paths = |d: &YamlDescender, q: &str| d.yaml_query(q).unwrap().iter().map(|(p, _)| d.format_path(p)).collect::<Vec<_>>();
+
+        assert_eq!(paths(&BashDescender, r#"GPIO.pins[*][?read-write=="ro"]"#), vec!["GPIO.pins[0].level", "GPIO.pins[1].level"]);
+        assert_eq!(paths(&BashDescender, "GPIO.pins[1][?offset>=0x28]"), vec!["GPIO.pins[1].clear", "GPIO.pins[1].level"]);
+        assert_eq!(paths(&BashDescender, "GPIO.pins[0][?read-write!=wo]").len(), 2);
+        // width and description are only present on the parent word
+        assert_eq!(paths(&BashDescender, "GPIO.pins[*][?width==32]"), vec!["GPIO.pins[0].function", "GPIO.pins[1].function"]);
+        assert_eq!(paths(&ZshDescender, "GPIO.pins@*@?description.bits"), vec!["GPIO.pins@0.function.bits", "GPIO.pins@1.function.bits"]);
+
+        let d = YamlDescender::new(r#"---
+pins:
+    - name: GPIO3
+      level: 1
+    - name: GPIO4
+      level: 0.5
+    - name: "GPIO 5"
+"#, true).unwrap();
+        assert_eq!(paths(&d, "pins[name=GPIO4].level"), vec!["pins[1].level"]);
+        assert_eq!(paths(&d, "pins[?level<1]"), vec!["pins[1]"]);
+        assert_eq!(paths(&d, r#"pins[name=="GPIO 5"]"#), vec!["pins[2]"]);
+        assert_eq!(paths(&d, "pins[?level]").len(), 2);
+        input_output_check(&d, "pins[name=GPIO4].", "pins[1].level\npins[1].name\n");
+
+        let path: YamlPath = r#"pins[name=="GPIO 5"].level"#.parse().unwrap();
+        assert_eq!(path.to_string(), r#"pins[?name=="GPIO 5"].level"#);
+        assert_eq!(path.to_string().parse::<YamlPath>().unwrap(), path);
+        assert!(matches!("pins[name=!3]".parse::<YamlPath>(), Err(YamlPathError::InvalidPath { position: 9, .. })));
+
+        let raw = yaml_query(&YamlData, "parent_test[?description]").unwrap();
+        assert_eq!(raw.len(), 3);
+    }
+
+    #[test]
+    fn test_set_and_remove() {
+        let mut yaml = YamlLoader::load_from_str(TEST_SOURCE).unwrap().remove(0);
+
+        *yaml_path_mut(&mut yaml, "root.array[0].number").unwrap() = Yaml::Integer(7);
+        assert_eq!(yaml_path(&yaml, "root.array[0].number").unwrap(), Yaml::Integer(7));
+
+        assert_eq!(set_at_path(&mut yaml, "root.array[-1].string", Yaml::Integer(1), false).unwrap(), Some(Yaml::String("str".to_string())));
+        assert_eq!(set_at_path(&mut yaml, "root.new", Yaml::Boolean(true), false).unwrap(), None);
+        assert!(matches!(set_at_path(&mut yaml, "root.a.b.c", Yaml::Null, false), Err(YamlPathError::KeyNotFound { .. })));
+        assert!(matches!(set_at_path(&mut yaml, "root.array[2]", Yaml::Null, false), Err(YamlPathError::IndexOutOfBounds { .. })));
+        assert!(matches!(set_at_path(&mut yaml, "root.array[*]", Yaml::Null, false), Err(YamlPathError::InvalidPath { .. })));
+        assert!(matches!(set_at_path(&mut yaml, "root.new.x", Yaml::Null, true), Err(YamlPathError::NotAHash { .. })));
+
+        set_at_path(&mut yaml, "root.a.b[2].c", Yaml::Integer(3), true).unwrap();
+        assert_eq!(yaml_path(&yaml, "root.a.b[2].c").unwrap(), Yaml::Integer(3));
+        assert_eq!(yaml_path(&yaml, "root.a.b[1]").unwrap(), Yaml::Null);
+        set_at_path(&mut yaml, "root.a.b[1].d", Yaml::Integer(4), true).unwrap();
+        assert_eq!(yaml_path(&yaml, "root.a.b[1].d").unwrap(), Yaml::Integer(4));
+
+        assert_eq!(remove_at_path(&mut yaml, "root.a.b[0]").unwrap(), Yaml::Null);
+        assert_eq!(yaml_path(&yaml, "root.a.b[1].c").unwrap(), Yaml::Integer(3));
+        remove_at_path(&mut yaml, "root.a").unwrap();
+        assert!(matches!(remove_at_path(&mut yaml, "root.a"), Err(YamlPathError::KeyNotFound { .. })));
+        assert!(matches!(remove_at_path(&mut yaml, ""), Err(YamlPathError::InvalidPath { .. })));
+
+        let mut d = YamlDescender::new_from_file("test_data.yaml", Dialect::JsonPointer).unwrap();
+        d.set_at_path("/GPIO/pins/0/set/bits", Yaml::String("0:3".to_string()), false).unwrap();
+        assert_eq!(d.yaml_descend_path("/GPIO/pins/0/set/bits").unwrap(), &Yaml::String("0:3".to_string()));
+        assert_eq!(yaml_path(d.document(), "dataroot.GPIO.pins[0].set.bits").unwrap(), Yaml::String("0:3".to_string()));
+        d.remove_at_path("/GPIO/pins/1").unwrap();
+        input_output_check(&d, "/GPIO/pins", "/GPIO/pins/0/clear\n/GPIO/pins/0/function\n/GPIO/pins/0/level\n/GPIO/pins/0/set\n");
+    }
+
+    #[test]
+    fn test_path_of() {
+        let d = &*ZshDescender;
+        let node = d.yaml_descend_path("GPIO.pins@1.set.bits").unwrap();
+        assert_eq!(d.path_of(node), Some("GPIO.pins@1.set.bits".to_string()));
+        assert_eq!(d.path_of(d.yaml_descend_path("GPIO").unwrap()), Some("GPIO".to_string()));
+        for (path, node) in d.yaml_query("**.offset").unwrap() {
+            assert_eq!(d.path_of(node), Some(d.format_path(&path)));
+        }
+        // a copy is not part of the tree
+        assert_eq!(d.path_of(&node.clone()), None);
+        // nor is anything above the root
+        assert_eq!(d.path_of(d.document()), None);
+
+        // changing the root changes every path
+        let mut rooted = YamlDescender::new("data:\n  pins:\n    - level: 1\n", true).unwrap();
+        assert_eq!(rooted.path_of(rooted.yaml_descend_path("data.pins[0]").unwrap()), Some("data.pins[0]".to_string()));
+        rooted.set_root("data").unwrap();
+        assert_eq!(rooted.path_of(rooted.yaml_descend_path("pins[0]").unwrap()), Some("pins[0]".to_string()));
+
+        let paths: Vec<(String, &Yaml)> = d.paths().collect();
+        assert_eq!(paths[0].0, "level1");
+        assert!(paths.iter().any(|(p, _)| p == "array@2@1"));
+        for (path, node) in &paths {
+            assert!(std::ptr::eq(d.yaml_descend_path(path.as_str()).unwrap(), *node));
+        }
+
+        let yaml = &*YamlData;
+        let node = &yaml["root"]["array"][0]["real"];
+        assert_eq!(yaml_path_of(yaml, node).unwrap().to_string(), "root.array[0].real");
+        assert_eq!(yaml_path_of(yaml, yaml), Some(YamlPath::new()));
+        assert_eq!(yaml_path_of(yaml, &Yaml::Null), None);
+    }
+
+    const MULTI_SOURCE: &str = r"---
+completion-metadata:
+  root: data
+---
+name: uart
+data:
+  baud: 9600
+  base:
+    description: the base
+  port:
+    parent: base
+  pin:
+    parent: base
+---
+name: spi
+data:
+  mode: 3
+  mask: 1
+---
+data:
+  other: 1
+" ;
+
+    #[test]
+    fn test_multiple_documents() {
+        let mut d = YamlDescender::new(MULTI_SOURCE, true).unwrap();
+        assert_eq!(d.document_count(), 3);
+        assert_eq!(d.document_name(1), Some("spi"));
+        assert_eq!(d.document_name(2), None);
+        assert_eq!(d.yaml_descend_path("baud").unwrap(), &Yaml::Integer(9600));
+
+        d.select_document_named("spi").unwrap();
+        assert_eq!(d.yaml_descend_path("mode").unwrap(), &Yaml::Integer(3));
+        assert!(d.yaml_descend_path("baud").is_err());
+        d.select_document(2).unwrap();
+        assert_eq!(d.yaml_descend_path("other").unwrap(), &Yaml::Integer(1));
+        assert!(matches!(d.select_document(3), Err(YamlPathError::IndexOutOfBounds { .. })));
+        assert!(matches!(d.select_document_named("i2c"), Err(YamlPathError::KeyNotFound { .. })));
+
+        input_output_check(&d, "", "uart::\nspi::\n2::\n");
+        input_output_check(&d, "s", "spi::mask\nspi::mode\n");
+        input_output_check(&d, "spi::mo", "spi::mode\n");
+        input_output_check(&d, "2::", "2::other\n");
+        input_output_check(&d, "i2c::", "");
+        // no document starts with o, so the selected one is completed
+        input_output_check(&d, "o", "other\n");
+        // what completion qualifies can be looked up
+        let completed = d.completions("spi::mo")[0].text.clone();
+        assert_eq!(d.yaml_descend_path(completed.as_str()).unwrap(), &Yaml::Integer(3));
+        assert_eq!(d.get_string_field_or_parent("uart::pin", "description"), Ok("the base".to_string()));
+        assert_eq!(d.yaml_descend_path("2::other").unwrap(), &Yaml::Integer(1));
+        assert!(d.yaml_descend_path("i2c::x").is_err());
+        // parents resolve in the document being completed
+        let mut writer = StrWriter::new();
+        d.write_completions(&mut writer, "uart::p", true).unwrap();
+        assert_eq!(writer.into_string().unwrap(), "aep-completion 1\nuart::pin\tthe base\tnospace\nuart::port\tthe base\tnospace\n");
+
+        d.complete_across_documents(false);
+        input_output_check(&d, "", "other\n");
+
+        // a single document keeps its metadata and is never qualified
+        let d = YamlDescender::new("completion-metadata:\n  root: data\ndata:\n  a: 1\n", true).unwrap();
+        assert_eq!(d.document_count(), 1);
+        input_output_check(&d, "", "a\n");
+
+        assert!(matches!(YamlDescender::new("", true), Err(YamlPathError::ParseError { .. })));
+        assert!(matches!(YamlDescender::new("# only a comment\n", true), Err(YamlPathError::ParseError { .. })));
+    }
+
+    const MULTI_PARENT_SOURCE: &str = r"---
+base:
+  description: base register
+  width: 32
+  access: rw
+access:
+  parent: base
+  access: ro
+timing:
+  width: 16
+  delay: 2
+register:
+  parent: [access, timing]
+bad:
+  parent: {a: b}
+";
+
+    #[test]
+    fn test_multiple_parents() {
+        let d = YamlDescender::new(MULTI_PARENT_SOURCE, true).unwrap();
+        assert_eq!(d.get_string_field_or_parent("register", "access"), Ok("ro".to_string()));
+        // depth first: access's own parent is searched before timing
+        assert_eq!(d.get_int_field_or_parent("register", "width"), Ok(32));
+        assert_eq!(d.get_int_field_or_parent("register", "delay"), Ok(2));
+        assert_eq!(d.get_description(d.yaml_descend_path("register").unwrap()), Ok("base register".to_string()));
+        assert!(matches!(d.get_int_field_or_parent("register", "missing"), Err(YamlPathError::KeyNotFound { .. })));
+        assert!(matches!(d.get_int_field_or_parent("bad", "width"),
+            Err(YamlPathError::TypeMismatch { expected: "a string or a list of strings", .. })));
+        assert_eq!(d.yaml_query("[?delay==2]").unwrap().len(), 2);
+
+        let yaml = &YamlLoader::load_from_str(MULTI_PARENT_SOURCE).unwrap()[0];
+        let register = yaml_path(yaml, "register").unwrap();
+        assert_eq!(yaml_field_parent(yaml, &register, "width"), Ok(Yaml::Integer(32)));
+        assert_eq!(yaml_field_parent(yaml, &register, "delay"), Ok(Yaml::Integer(2)));
+
+        // each level reaches the next through two parents, 2^40 paths to n40
+        // that are only searched once
+        let diamond: String = (0..40).map(|k| format!("n{}: {{parent: [n{}, n{}]}}\n", k, k + 1, k + 1)).collect::<String>() + "n40: {width: 1}\n";
+        let d = YamlDescender::new(&diamond, true).unwrap();
+        assert!(matches!(d.get_int_field_or_parent("n0", "missing"), Err(YamlPathError::KeyNotFound { .. })));
+        assert_eq!(d.get_int_field_or_parent("n0", "width"), Ok(1));
+        assert!(d.parent_cycles().is_empty());
+        let mut writer = StrWriter::new();
+        d.write_completions(&mut writer, "n0", true).unwrap();
+    }
+
+    const CYCLE_SOURCE: &str = r"---
+a:
+  parent: b
+b:
+  parent: [c, a]
+c:
+  width: 1
+self:
+  parent: self
+entry:
+  parent: a
+chain0:
+  parent: chain1
+chain1:
+  parent: chain2
+chain2:
+  parent: chain3
+chain3:
+  depth: 3
+";
+
+    #[test]
+    fn test_parent_cycles() {
+        let mut d = YamlDescender::new(CYCLE_SOURCE, true).unwrap();
+        // found before the cycle is reached
+        assert_eq!(d.get_int_field_or_parent("a", "width"), Ok(1));
+        let e = d.get_int_field_or_parent("a", "missing").unwrap_err();
+        assert_eq!(e, YamlPathError::ParentCycle { path: "a -> b -> a".to_string(), segment: "a".to_string() });
+        assert_eq!(e.to_string(), "parent cycle a -> b -> a");
+        assert_eq!(d.get_string_field_or_parent("a", "missing"), Err(e));
+        let missing = d.get_string_field_or_parent("c", "missing").unwrap_err();
+        assert_eq!(missing, YamlPathError::KeyNotFound { path: "c".to_string(), segment: "missing".to_string() });
+        assert_eq!(missing.to_string(), "missing not found in c");
+        let e = BashDescender.get_int_field_or_parent("GPIO.pins", "level").unwrap_err();
+        assert_eq!((e.path(), e.to_string().as_str()), ("GPIO.pins", "GPIO.pins is not a hash"));
+        let e = BashDescender.get_bool_field_or_parent("GPIO.pins[0]", "missing").unwrap_err();
+        assert_eq!(e.path(), "GPIO.pins[0]");
+        assert!(matches!(d.get_string_field_or_parent("c", "width"), Err(YamlPathError::TypeMismatch { expected: "a string", .. })));
+        assert_eq!(d.get_int_field_or_parent("self", "x").unwrap_err().path(), "self -> self");
+        assert_eq!(d.get_int_field_or_parent("entry", "x").unwrap_err().path(), "a -> b -> a");
+        assert!(matches!(d.get_description(d.yaml_descend_path("a").unwrap()), Err(YamlPathError::ParentCycle { .. })));
+        // completion survives a cycle
+        assert!(d.write_completions(&mut StrWriter::new(), "", true).is_ok());
+
+        let problems: Vec<String> = d.parent_cycles().iter().map(|e| e.path().to_string()).collect();
+        assert_eq!(problems, vec!["a -> b -> a", "self -> self"]);
+        assert_eq!(parent_cycles(&YamlLoader::load_from_str(CYCLE_SOURCE).unwrap()[0]).len(), 2);
+
+        assert_eq!(d.get_int_field_or_parent("chain0", "depth"), Ok(3));
+        d.set_max_parent_depth(2);
+        let e = d.get_int_field_or_parent("chain0", "depth").unwrap_err();
+        assert_eq!(e, YamlPathError::ParentDepthExceeded { path: "chain0 -> chain1 -> chain2 -> chain3".to_string(), segment: "chain3".to_string(), limit: 2 });
+        assert_eq!(e.to_string(), "parent chain chain0 -> chain1 -> chain2 -> chain3 is longer than 2");
+        assert_eq!(d.get_int_field_or_parent("chain1", "depth"), Ok(3));
+        assert_eq!(d.parent_cycles().len(), 4);
+        // a node reaching the middle of a chain first does not hide the chain
+        let mut d = YamlDescender::new("z: {parent: c2}\nc0: {parent: c1}\nc1: {parent: c2}\nc2: {parent: c3}\nc3: {x: 1}\n", true).unwrap();
+        d.set_max_parent_depth(2);
+        let problems: Vec<String> = d.parent_cycles().iter().map(|e| e.path().to_string()).collect();
+        assert_eq!(problems, vec!["c0 -> c1 -> c2 -> c3"]);
+
+        let yaml = &YamlLoader::load_from_str(CYCLE_SOURCE).unwrap()[0];
+        assert!(matches!(yaml_field_parent(yaml, &yaml["a"], "x"), Err(YamlPathError::ParentCycle { .. })));
+    }
+
+    const RELATIVE_SOURCE: &str = r#"---
+completion-metadata:
+  root: chip
+chip:
+  GPIO:
+    words:
+      set0:
+        description: set word
+        width: 32
+    pins:
+      - set:
+          parent: ...words.set0
+        clear:
+          parent: .set
+        level:
+          parent: $.GPIO.words.set0
+      - set:
+          parent: ../0/set
+  .dotted:
+    width: 8
+  odd:
+    parent: $.".dotted"
+  odder:
+    parent: "'.dotted'"
+"#;
+
+    #[test]
+    fn test_relative_parents() {
+        let d = YamlDescender::new(RELATIVE_SOURCE, true).unwrap();
+        assert_eq!(d.get_int_field_or_parent("GPIO.pins[0].set", "width"), Ok(32));
+        assert_eq!(d.get_int_field_or_parent("GPIO.pins[0].clear", "width"), Ok(32));
+        assert_eq!(d.get_int_field_or_parent("GPIO.pins[0].level", "width"), Ok(32));
+        assert_eq!(d.get_int_field_or_parent("odd", "width"), Ok(8));
+        assert_eq!(d.get_int_field_or_parent("odder", "width"), Ok(8));
+        // `..` is the pins array, whose element 0 holds the first set
+        assert_eq!(d.get_int_field_or_parent("GPIO.pins[1].set", "width"), Ok(32));
+        assert_eq!(d.get_description(d.yaml_descend_path("GPIO.pins[0].clear").unwrap()), Ok("set word".to_string()));
+        let above = YamlDescender::new("a:\n  parent: ..b\nb: {}\n", true).unwrap();
+        assert!(matches!(above.get_int_field_or_parent("a", "width"), Err(YamlPathError::InvalidPath { .. })));
+        // a copy has no place in the tree to be relative to
+        let copy = d.yaml_descend_path("GPIO.pins[0].set").unwrap().clone();
+        assert!(d.get_field_or_parent(&copy, "width").is_err());
+        assert_eq!(d.yaml_query("GPIO.pins[*][?width==32]").unwrap().len(), 4);
+
+        // yaml_field_parent resolves from the root it is given in the same way
+        let yaml = &YamlLoader::load_from_str(RELATIVE_SOURCE).unwrap()[0]["chip"];
+        assert_eq!(yaml_field_parent(yaml, &yaml["GPIO"]["pins"][0]["clear"], "width"), Ok(Yaml::Integer(32)));
+
+        let cycle = YamlDescender::new("a:\n  b:\n    parent: .c\n  c:\n    parent: .b\n", true).unwrap();
+        assert_eq!(cycle.parent_cycles()[0].path(), "a.b -> a.c -> a.b");
+    }
+
+    #[test]
+    fn test_flatten() {
+        let d = YamlDescender::new(RELATIVE_SOURCE, true).unwrap();
+        let flat = d.flattened(FlattenOptions::default()).unwrap();
+        let set = &flat["GPIO"]["pins"][0]["set"];
+        assert_eq!(set["width"], Yaml::Integer(32));
+        assert_eq!(set["description"].as_str(), Some("set word"));
+        assert_eq!(set["parent"], Yaml::BadValue);
+        assert_eq!(flat["GPIO"]["pins"][1]["set"]["width"], Yaml::Integer(32));
+        assert_eq!(flat["odd"]["width"], Yaml::Integer(8));
+        assert!(yaml_query(&flat, "**.parent").unwrap().is_empty());
+
+        let flat = d.flattened(FlattenOptions { keep_parent: true, keep_description: false }).unwrap();
+        let set = &flat["GPIO"]["pins"][1]["set"];
+        assert_eq!(set["parent"].as_str(), Some("../0/set"));
+        assert_eq!(set["description"], Yaml::BadValue);
+        // own members first, then inherited ones in lookup order
+        let keys: Vec<&str> = set.as_hash().unwrap().keys().filter_map(|k| k.as_str()).collect();
+        assert_eq!(keys, vec!["parent", "width"]);
+
+        let yaml = &YamlLoader::load_from_str(MULTI_PARENT_SOURCE).unwrap()[0];
+        assert!(matches!(flatten(yaml, FlattenOptions::default()), Err(YamlPathError::TypeMismatch { .. })));
+        let yaml = &YamlLoader::load_from_str(CYCLE_SOURCE).unwrap()[0];
+        assert!(matches!(flatten(yaml, FlattenOptions::default()), Err(YamlPathError::ParentCycle { .. })));
+        // no chain of parent references loops, but b.x inherits a which inherits b.x
+        let yaml = &YamlLoader::load_from_str("a: {parent: b}\nb: {x: {parent: a}}\n").unwrap()[0];
+        assert!(parent_cycles(yaml).is_empty());
+        match flatten(yaml, FlattenOptions::default()) {
+            Err(YamlPathError::ParentCycle { path, .. }) => assert_eq!(path, "b.x -> b.x"),
+            other => panic!("expected a parent cycle, got {:?}", other),
+        }
+        // test_data.yaml refers to a missing GPIO.words.set0
+        assert!(matches!(BashDescender.flattened(FlattenOptions::default()), Err(YamlPathError::KeyNotFound { .. })));
+    }
+
+    #[test]
+    fn test_provenance() {
+        let d = YamlDescender::new(MULTI_PARENT_SOURCE, false).unwrap();
+        let p = d.get_field_with_provenance("register", "delay").unwrap();
+        assert_eq!(p.value, Yaml::Integer(2));
+        assert_eq!(p.visited, vec!["register", "access", "base", "timing"]);
+        assert_eq!(p.found_at, "timing");
+        assert!(p.is_inherited());
+        assert_eq!(p.explain(), "delay of register is 2\n  register: not set\n  access: not set\n  base: not set\n  timing: found\n");
+
+        let p = d.get_field_with_provenance("timing", "width").unwrap();
+        assert!(!p.is_inherited());
+        assert_eq!(p.found_at, "timing");
+
+        let e = d.get_field_with_provenance("register", "missing").unwrap_err();
+        assert_eq!(e, YamlPathError::KeyNotFound { path: "register".to_string(), segment: "missing".to_string() });
+
+        let d = YamlDescender::new(RELATIVE_SOURCE, false).unwrap();
+        let p = d.get_field_with_provenance("GPIO.pins@1.set", "description").unwrap();
+        assert_eq!(p.visited, vec!["GPIO.pins@1.set", "GPIO.pins@0.set", "GPIO.words.set0"]);
+        assert_eq!(p.value, Yaml::String("set word".to_string()));
+    }
+
+    const MERGE_SOURCE: &str = r"---
+base:
+  timing: {setup: 1, hold: 2, edges: [rise, fall]}
+  masks: [{bits: 1, name: a}, {bits: 2}]
+left:
+  parent: base
+  timing: {hold: 3}
+right:
+  parent: base
+  timing: {pulse: 4, edges: [both]}
+reg:
+  parent: [left, right]
+  masks: [{bits: 5}]
+";
+
+    #[test]
+    fn test_deep_merge() {
+        let mut d = YamlDescender::new(MERGE_SOURCE, true).unwrap();
+        let reg = d.yaml_descend_path("reg").unwrap().clone();
+        assert_eq!(d.get_field_or_parent(&reg, "timing").unwrap()["setup"], Yaml::BadValue);
+
+        d.set_inheritance(Inheritance::DeepMerge(ArrayMerge::Replace));
+        let reg = d.yaml_descend_path("reg").unwrap();
+        let timing = d.get_field_or_parent(reg, "timing").unwrap();
+        assert_eq!(timing["setup"], Yaml::Integer(1));
+        assert_eq!(timing["hold"], Yaml::Integer(3));
+        assert_eq!(timing["pulse"], Yaml::Integer(4));
+        // depth first, base is nearer than right
+        assert_eq!(timing["edges"].as_vec().unwrap().len(), 2);
+        let masks = d.get_field_or_parent(reg, "masks").unwrap();
+        assert_eq!(masks.as_vec().unwrap().len(), 1);
+        assert_eq!(d.get_int_field_or_parent("reg", "timing"), Err(YamlPathError::TypeMismatch {
+            path: "reg".to_string(), segment: "timing".to_string(), expected: "an integer" }));
+
+        d.set_inheritance(Inheritance::DeepMerge(ArrayMerge::Append));
+        let reg = d.yaml_descend_path("reg").unwrap();
+        // base is reached through both parents but merged once
+        let edges = d.get_field_or_parent(reg, "timing").unwrap()["edges"].clone();
+        assert_eq!(edges, Yaml::Array(vec![Yaml::String("both".to_string()), Yaml::String("rise".to_string()), Yaml::String("fall".to_string())]));
+        assert_eq!(d.get_field_or_parent(reg, "masks").unwrap().as_vec().unwrap().len(), 3);
+
+        d.set_inheritance(Inheritance::DeepMerge(ArrayMerge::ByIndex));
+        let reg = d.yaml_descend_path("reg").unwrap();
+        let masks = d.get_field_or_parent(reg, "masks").unwrap();
+        assert_eq!(masks[0]["bits"], Yaml::Integer(5));
+        assert_eq!(masks[0]["name"].as_str(), Some("a"));
+        assert_eq!(masks[1]["bits"], Yaml::Integer(2));
+
+        let yaml = &YamlLoader::load_from_str(MERGE_SOURCE).unwrap()[0];
+        let timing = yaml_field_parent_with(yaml, &yaml["left"], "timing", Inheritance::DeepMerge(ArrayMerge::Replace)).unwrap();
+        assert_eq!(timing["setup"], Yaml::Integer(1));
+        assert_eq!(yaml_field_parent(yaml, &yaml["left"], "timing").unwrap()["setup"], Yaml::BadValue);
+    }
+
+    const METADATA_SOURCE: &str = r"---
+completion-metadata:
+    root: regs
+    terminus: reset
+    ignore-fields: [notes]
+    descriptions: true
+regs:
+    ctrl:
+        reset: 0
+        bits: 8
+    status:
+        notes: read only
+        parent: ctrl
+        description: status register
+        mode: {}
+        width: 8
+";
+
+    #[test]
+    fn test_metadata() {
+        let d = YamlDescender::new(METADATA_SOURCE, true).unwrap();
+        let metadata = d.get_metadata();
+        assert_eq!(metadata.root, "regs");
+        assert!(metadata.has_terminus());
+        assert!(metadata.get_descriptions);
+        assert!(metadata.has_terminal_field(d.yaml_descend_path("ctrl").unwrap()));
+        assert!(!metadata.has_terminal_field(d.yaml_descend_path("status").unwrap()));
+        // descriptions are always on so the protocol is written, the
+        // terminus makes ctrl a leaf, notes is ignored as well as parent
+        // and description
+        assert_eq!(metadata.ignore_fields.len(), 4);
+        input_output_check(&d, "c", "aep-completion 1\nctrl\t\t\n");
+        input_output_check(&d, "status.", "aep-completion 1\nstatus.mode\t\tnospace\nstatus.width\t\t\n");
+
+        let d = YamlDescender::new("completion-metadata: {root: regs}\nregs: {a: {parent: b, description: x, c: 1, d: 2}}", true).unwrap();
+        assert_eq!(d.get_metadata().ignore_fields.len(), 3);
+        input_output_check(&d, "a.", "a.c\na.d\n");
+
+        assert_eq!(Metadata::from_yaml(&Yaml::Null), Ok(Metadata::default()));
+        assert_eq!(YamlDescender::new("completion-metadata: {terminus: [a]}", true).err(), Some(YamlPathError::TypeMismatch {
+            path: "completion-metadata".to_string(), segment: "terminus".to_string(), expected: "a string" }));
+        assert!(YamlDescender::new("completion-metadata: {ignore-fields: [1]}", true).is_err());
+    }
+
+    #[test]
+    fn test_validate_metadata() {
+        let docs = YamlLoader::load_from_str(r"---
+completion-metadata:
+    root: data
+    terminal-feilds: [bits]
+    terminus: [reset]
+    ignore-fields: parent
+    descriptions: yes please
+    previous: {}
+---
+data: {a: 1}
+---
+other: {a: 2}
+").unwrap();
+        let problems = Metadata::validate(&docs);
+        let keys: Vec<String> = problems.iter().map(|p| format!("{}.{}", p.path(), p.segment())).collect();
+        assert_eq!(keys, vec!["completion-metadata.terminal-feilds", "completion-metadata.terminus",
+                              "completion-metadata.ignore-fields", "completion-metadata.descriptions",
+                              "completion-metadata.root.data"]);
+        assert_eq!(problems[0], YamlPathError::UnknownKey { path: "completion-metadata".to_string(), segment: "terminal-feilds".to_string() });
+        assert_eq!(problems[4].to_string(), "data not found in completion-metadata.root");
+
+        let good = YamlLoader::load_from_str("completion-metadata: {root: data, previous: {}}\ndata: {a: 1}").unwrap();
+        assert!(Metadata::validate(&good).is_empty());
+        assert!(Metadata::validate(&YamlLoader::load_from_str("completion-metadata: 3").unwrap())[0].segment() == "completion-metadata");
+        assert!(YamlDescender::new_from_file_strict("test_data.yaml", true).is_ok());
+
+        match YamlDescender::new_strict("completion-metadata: {rot: data}\ndata: {a: 1}", true) {
+            Err(e @ YamlPathError::InvalidMetadata { .. }) => assert_eq!(e.to_string(), "invalid completion-metadata: rot is not a known key of completion-metadata"),
+            _ => panic!("expected InvalidMetadata"),
+        }
+    }
+
+    #[test]
+    fn test_previous_actions() {
+        let dir = write_files("previous", &[("conf/main.yaml", "a: 1\n"), ("conf/more.yml", "b: 2\n"), ("conf/sub/x.yaml", "")]);
+        let conf = format!("{}/conf/", dir.display());
+        let after = |d: &YamlDescender, previous: &str, current: &str| {
+            let mut writer = StrWriter::new();
+            d.write_completions_after(&mut writer, previous, current, false).unwrap();
+            writer.into_string().unwrap()
+        };
+
+        assert_eq!(BashDescender.previous_action("-f"), Some(&PreviousAction::Reload));
+        assert_eq!(after(&BashDescender, "-f", &format!("{}m", conf)), format!("{0}main.yaml\n{0}more.yml\n", conf));
+        // a word with no action completes paths
+        assert_eq!(after(&BashDescender, "-x", "level1.level2a"), "level1.level2a\n");
+
+        let d = YamlDescender::new(r"---
+completion-metadata:
+    previous:
+        -C: {action: directories}
+        -o: {action: files}
+        --mode: {action: enum, values: [slow, fast, safe]}
+        --word: {action: root, root: regs.words}
+regs:
+    words: {set0: {bits: 1}, clear0: {bits: 2}}
+    pins: {pin0: {}}
+", true).unwrap();
+        assert_eq!(after(&d, "-C", &conf), format!("{}sub/\n", conf));
+        assert_eq!(after(&d, "-o", &format!("{}s", conf)), format!("{}sub/\n", conf));
+        assert_eq!(after(&d, "--mode", "s"), "safe\nslow\n");
+        assert_eq!(after(&d, "--word", ""), "clear0\nset0\n");
+        assert_eq!(after(&d, "--word", "set0."), "set0.bits\n");
+        assert_eq!(after(&d, "--words", "r"), "regs.pins\nregs.words\n");
+
+        let docs = YamlLoader::load_from_str("completion-metadata: {previous: {-x: {action: teleport}, -y: {action: enum}, -z: files}}").unwrap();
+        let keys: Vec<String> = Metadata::validate(&docs).iter().map(|p| format!("{}.{}", p.path(), p.segment())).collect();
+        assert_eq!(keys, vec!["completion-metadata.previous.-x.action", "completion-metadata.previous.-y.values", "completion-metadata.previous.-z"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    const HINT_SOURCE: &str = r"---
+completion-metadata:
+    root: regs
+regs:
+    words:
+        set0: {bits: 1}
+        clear0: {bits: 2}
+    modes: [fast, slow, safe]
+    base:
+        completion: {type: ref, from: modes}
+    uart:
+        parent: base
+        speed:
+            completion: {type: enum, values: ['9600', '19200', '115200']}
+        word:
+            completion: {type: ref, from: words}
+        log:
+            completion: {type: file}
+        bad:
+            completion: {type: teleport}
+    pins:
+        - name: a
+          mode:
+            completion: {type: ref, from: modes}
+";
+
+    #[test]
+    fn test_value_hints() {
+        let d = YamlDescender::new(HINT_SOURCE, true).unwrap();
+        assert_eq!(d.value_hint("uart.log").unwrap(), Some(ValueHint::File));
+        assert_eq!(d.value_hint("uart").unwrap(), Some(ValueHint::Ref("modes".to_string())));
+        assert_eq!(d.value_hint("words.set0").unwrap(), None);
+        assert_eq!(d.value_hint("uart.bad").err().map(|e| e.path().to_string()), Some("uart.bad".to_string()));
+
+        input_output_check(&d, "uart.speed=1", "uart.speed=115200\nuart.speed=19200\n");
+        input_output_check(&d, "uart.word=", "uart.word=clear0\nuart.word=set0\n");
+        // inherited from base through parent
+        input_output_check(&d, "uart=s", "uart=safe\nuart=slow\n");
+        // the value follows the last `=` outside a filter
+        input_output_check(&d, "pins[name=a].mode=f", "pins[name=a].mode=fast\n");
+        assert_eq!(split_value(r#"pins["a=b"].x='c=d'=e"#), Some((r#"pins["a=b"].x='c=d'"#, "e")));
+        assert_eq!(split_value(r"a\=b"), None);
+        // completion is not offered as a key
+        input_output_check(&d, "uart.", "uart.bad\nuart.log\nuart.speed\nuart.word\n");
+
+        let mut writer = StrWriter::new();
+        d.write_completions_after(&mut writer, "uart.speed", "9", false).unwrap();
+        assert_eq!(writer.into_string().unwrap(), "9600\n");
+        let mut writer = StrWriter::new();
+        d.write_completions_after(&mut writer, "words", "w", false).unwrap();
+        assert_eq!(writer.into_string().unwrap(), "words.clear0\nwords.set0\n");
+    }
+
+    #[test]
+    fn test_completion_model() {
+        let d = YamlDescender::new(METADATA_SOURCE, true).unwrap();
+        let completions = d.completions("");
+        assert_eq!(completions, vec![
+            Completion { text: "ctrl".to_string(), display: "ctrl".to_string(), description: None,
+                         kind: NodeKind::Hash, terminal: true, no_space: false },
+            Completion { text: "status".to_string(), display: "status".to_string(), description: Some("status register".to_string()),
+                         kind: NodeKind::Hash, terminal: false, no_space: true },
+        ]);
+        // descended automatically to the only match
+        let completions = d.completions("status.w");
+        assert_eq!((completions[0].text.as_str(), completions[0].display.as_str()), ("status.width", "width"));
+
+        let completions = ZshDescender.completions("GPIO.pins@");
+        assert!(completions.iter().all(|c| c.kind == NodeKind::Hash && c.display.starts_with('@')));
+        let completions = BashDescender.completions("level1.level2a");
+        assert_eq!(completions[0].kind, NodeKind::Leaf);
+        assert!(completions[0].terminal && !completions[0].no_space);
+
+        let d = YamlDescender::new(HINT_SOURCE, true).unwrap();
+        let completions = d.completions("uart.speed=9");
+        assert_eq!(completions, vec![Completion { text: "uart.speed=9600".to_string(), display: "uart.speed=9600".to_string(),
+                                                  description: None, kind: NodeKind::Leaf, terminal: true, no_space: false }]);
+        assert_eq!(d.completions_after("uart.word", "s")[0].text, "set0");
+    }
+
+    #[test]
+    fn test_completion_protocol() {
+        let d = YamlDescender::new("a:\n  description: \"two\\nlines\\twith\\\\tabs\"\n  b: 1\nc: 2\n", true).unwrap();
+        let mut writer = StrWriter::new();
+        d.write_completions(&mut writer, "", true).unwrap();
+        let out = writer.into_string().unwrap();
+        assert_eq!(out, "aep-completion 1\na\ttwo\\nlines\\twith\\\\tabs\tnospace\nc\t\t\n");
+        let fields: Vec<&str> = out.lines().nth(1).unwrap().split('\t').collect();
+        assert_eq!(unescape(fields[1]), "two\nlines\twith\\tabs");
+        assert_eq!(escape("a\\b\r"), "a\\\\b\\r");
+        assert_eq!(unescape("\\q\\"), "\\q\\");
+        assert!(BASH_FUNCTION.contains(PROTOCOL_HEADER) && ZSH_FUNCTION.contains(PROTOCOL_HEADER));
+        // the command sees the words as typed, without the shell's quoting
+        assert!(ZSH_FUNCTION.contains(r#""${(Q)words[CURRENT-1]}" "${(Q)words[CURRENT]}""#));
+    }
+
+    /// write `files` into a fresh directory under the temp dir
+    fn write_files(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("aep-{}-{}", name, std::process::id()));
+        for (file, content) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include_files() {
+        let dir = write_files("include", &[
+            ("main.yaml", "GPIO:\n  include: lib/regs.yaml\n  base: 0x1000\nTIMER:\n  parent: lib/common.yaml#words.set0\nUART:\n  include: lib/common.yaml#words\n"),
+            ("lib/regs.yaml", "base: 0\nset0:\n  parent: common.yaml#words.set0\n"),
+            ("lib/common.yaml", "words:\n  set0:\n    description: set bits\n    width: 32\n"),
+            ("a.yaml", "include: b.yaml\n"),
+            ("b.yaml", "x:\n  include: a.yaml\n"),
+            ("missing.yaml", "x:\n  parent: none.yaml#y\n"),
+        ]);
+        let main = dir.join("main.yaml");
+        let d = YamlDescender::new_from_file(main.to_str().unwrap(), true).unwrap();
+        // the including file's keys win
+        assert_eq!(d.get_int_field_or_parent("GPIO", "base"), Ok(0x1000));
+        // found from lib/regs.yaml's directory
+        assert_eq!(d.get_string_field_or_parent("GPIO.set0", "description").unwrap(), "set bits");
+        assert_eq!(d.get_int_field_or_parent("TIMER", "width"), Ok(32));
+        assert_eq!(d.get_string_field_or_parent("UART.set0", "description").unwrap(), "set bits");
+        let provenance = d.get_field_with_provenance("GPIO.set0", "width").unwrap();
+        assert!(provenance.is_inherited());
+
+        let docs = load_file(&main).unwrap();
+        // kept as written, still naming lib/common.yaml now it is mounted in main.yaml
+        assert_eq!(docs[0]["GPIO"]["set0"]["parent"].as_str(), Some("common.yaml#words.set0"));
+        assert_eq!(d.yaml_descend_path("GPIO.set0.parent").unwrap().as_str(), Some("common.yaml#words.set0"));
+        let flat = d.flattened(FlattenOptions { keep_parent: true, keep_description: true }).unwrap();
+        assert_eq!(flat["GPIO"]["set0"]["parent"].as_str(), Some("common.yaml#words.set0"));
+        assert_eq!(flat["GPIO"]["set0"]["width"], Yaml::Integer(32));
+
+        match load_file(dir.join("a.yaml")) {
+            Err(YamlPathError::ParseError { path, message, .. }) => {
+                assert!(path.ends_with("a.yaml"), "{}", path);
+                assert!(message.starts_with("include cycle"), "{}", message);
+            }
+            r => panic!("expected an include cycle, got {:?}", r),
+        }
+        match YamlDescender::new_from_file(dir.join("nowhere.yaml").to_str().unwrap(), true) {
+            Err(YamlPathError::ParseError { path, .. }) => assert!(path.ends_with("nowhere.yaml")),
+            _ => panic!("expected a ParseError"),
+        }
+        let d = YamlDescender::new_from_file(dir.join("missing.yaml").to_str().unwrap(), true).unwrap();
+        match d.get_field_or_parent(d.yaml_descend_path("x").unwrap(), "description") {
+            Err(YamlPathError::ParseError { path, .. }) => assert!(path.ends_with("none.yaml"), "{}", path),
+            r => panic!("expected a ParseError, got {:?}", r),
+        }
+
+        // a string is only read as a file when given a directory
+        let source = "GPIO:\n  include: lib/regs.yaml\n  base: 0x1000\n";
+        let d = YamlDescender::new(source, true).unwrap();
+        assert_eq!(d.get_string_field_or_parent("GPIO", "include"), Ok("lib/regs.yaml".to_string()));
+        input_output_check(&d, "GPIO.", "GPIO.base\nGPIO.include\n");
+        let d = YamlDescender::new_with_base(source, true, &dir).unwrap();
+        assert_eq!(d.get_int_field_or_parent("GPIO.set0", "width"), Ok(32));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_fish_dialect() {
+        assert_eq!("fish".parse::<Dialect>(), Ok(Dialect::Fish));
+        assert_eq!(Dialect::Fish.to_string(), "fish");
+        assert_eq!(Dialect::Fish.format(&Dialect::Fish.parse("GPIO.pins@1.set").unwrap()), "GPIO.pins@1.set");
+
+        let d = YamlDescender::new_from_file("test_data.yaml", Dialect::Fish).unwrap();
+        input_output_check(&d, "GPIO.pins@-1.s", "GPIO.pins@1.set.bits\nGPIO.pins@1.set.offset\nGPIO.pins@1.set.read-write\n");
+        let d = YamlDescender::new("a:\n  description: \"two\\nlines\"\n  b: 1\nc: {description: see, d: [1, 2]}\n", Dialect::Fish).unwrap();
+        let mut writer = StrWriter::new();
+        d.write_completions(&mut writer, "", true).unwrap();
+        assert_eq!(writer.into_string().unwrap(), "a\ttwo lines\nc\tsee\n");
+        let mut writer = StrWriter::new();
+        d.write_completions(&mut writer, "c.d", true).unwrap();
+        assert_eq!(writer.into_string().unwrap(), "c.d@0\nc.d@1\n");
+
+        // keys escape what fish would expand and still parse back
+        let key = "irq(*)${a?}";
+        let formatted = Dialect::Fish.array_parser().format_key(key);
+        assert_eq!(formatted, r"irq\(\*\)\$\{a\?\}");
+        assert_eq!(Dialect::Fish.parse(&formatted).unwrap().segments(), &[Segment::Key(key.to_string())]);
+        assert_eq!(Dialect::Zsh.array_parser().format_key(key), key);
+
+        let script = fish_script("reg-tool", &["reg-tool", "--complete"]);
+        assert!(script.contains("function __reg_tool_complete\n"));
+        assert!(script.contains("'reg-tool' '--complete' $tokens[-1] (commandline -ct)"));
+        assert!(script.ends_with("complete -c 'reg-tool' -f -a '(__reg_tool_complete)'\n"));
+        let script = fish_script("it's; rm", &["it's", r"a\b"]);
+        assert!(script.contains(r"'it\'s' 'a\\b' $tokens"));
+        assert!(script.contains(r"complete -c 'it\'s; rm' -f"));
+    }
+
+    #[test]
+    fn test_matchers() {
+        let prefix = PrefixMatcher::default();
+        let ignore_case = IgnoreCasePrefixMatcher::default();
+        let substring = SubstringMatcher::default();
+        let fuzzy = SubsequenceMatcher::default();
+        assert_eq!(prefix.score("GPIO", "gpio"), None);
+        assert!(ignore_case.score("GPIO", "GP") > ignore_case.score("gpio", "GP"));
+        assert!(substring.score("pio", "io") > substring.score("GPIO", "io"));
+        assert_eq!(substring.score("GPIO", "x"), None);
+        assert!(fuzzy.score("read-write", "rw") > fuzzy.score("rewind", "rw"));
+        assert!(fuzzy.score("offset", "off") > fuzzy.score("of_fset", "off"));
+        assert_eq!(fuzzy.score("GPIO", "gpx"), None);
+
+        let d = YamlDescender::new_from_file("test_data.yaml", true).unwrap();
+        input_output_check(&d, "gpi", "GPIO.pins\nGPIO.words\n");
+        input_output_check(&d, "GPIO.pins[1].set.write", "GPIO.pins[1].set.read-write\n");
+        input_output_check(&d, "GPIO.pins[1].set.rw", "GPIO.pins[1].set.read-write\n");
+
+        let s = "pins: {pin10: 1, pin2: 2, apin: 3, spinner: 4}\n";
+        let mut d = YamlDescender::new(s, true).unwrap();
+        input_output_check(&d, "pins.pin", "pins.pin10\npins.pin2\n");
+        input_output_check(&d, "pins.in", "pins.pin10\npins.pin2\npins.apin\npins.spinner\n");
+        d.set_match_mode(MatchMode::Substring);
+        input_output_check(&d, "pins.pin", "pins.pin10\npins.pin2\npins.apin\npins.spinner\n");
+        d.set_match_mode(MatchMode::Prefix);
+        input_output_check(&d, "pins.in", "");
+        d.set_matchers(vec![Box::new(SubsequenceMatcher::default())]);
+        input_output_check(&d, "pins.pn", "pins.pin10\npins.pin2\npins.apin\npins.spinner\n");
+
+        let d = YamlDescender::new(HINT_SOURCE, true).unwrap();
+        let texts = |c: Vec<Completion>| c.into_iter().map(|c| c.text).collect::<Vec<_>>();
+        assert_eq!(texts(d.completions("uart.speed=96")), vec!["uart.speed=9600"]);
+        assert_eq!(texts(d.completions("uart.speed=00")), vec!["uart.speed=9600", "uart.speed=19200", "uart.speed=115200"]);
+        assert_eq!(texts(d.completions("uart.word=SET")), vec!["uart.word=set0"]);
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        let d = YamlDescender::new_from_file("test_data.yaml", Dialect::JsonPointer).unwrap();
+        assert_eq!(d.dialect(), Dialect::JsonPointer);
+        assert_eq!(d.yaml_descend_path("/GPIO/pins/1/set/bits").unwrap(), &Yaml::String("1:1".to_string()));
+        assert_eq!(d.get_int_field_or_parent("/GPIO/pins/0/function", "width"), Ok(32));
+
+        let path = Dialect::JsonPointer.parse("/a~1b/~0c/0/01").unwrap();
+        assert_eq!(path.segments(), &[Segment::Key("a/b".to_string()), Segment::Key("~c".to_string()),
+            Segment::Index(0), Segment::Key("01".to_string())]);
+        assert_eq!(Dialect::JsonPointer.format(&path), "/a~1b/~0c/0/01");
+        assert_eq!(Dialect::JsonPointer.parse("").unwrap(), YamlPath::new());
+        assert!(matches!(Dialect::JsonPointer.parse("GPIO"), Err(YamlPathError::InvalidPath { position: 0, .. })));
+        assert!(matches!(Dialect::JsonPointer.parse("/a~2"), Err(YamlPathError::InvalidPath { position: 2, .. })));
+
+        input_output_check(&d, "/G", "/GPIO/pins\n/GPIO/words\n");
+        input_output_check(&d, "/GPIO/pins", "/GPIO/pins/0\n/GPIO/pins/1\n");
+        input_output_check(&d, "/GPIO/pins/1/c", "/GPIO/pins/1/clear/bits\n/GPIO/pins/1/clear/offset\n/GPIO/pins/1/clear/read-write\n");
+        input_output_check(&d, "/array/2/", "/array/2/0\n/array/2/1\n/array/2/2\n");
+
+        let numbered = YamlDescender::new("0: zero\n'1': one\nx/y: slash", Dialect::JsonPointer).unwrap();
+        assert_eq!(numbered.yaml_descend_path("/0").unwrap(), &Yaml::String("zero".to_string()));
+        assert_eq!(numbered.yaml_descend_path("/1").unwrap(), &Yaml::String("one".to_string()));
+        assert_eq!(numbered.yaml_descend_path("/x~1y").unwrap(), &Yaml::String("slash".to_string()));
+
+        // pointer parent references work whatever the dialect
+        let d = YamlDescender::new("base:\n  key: value\nchild:\n  parent: /base\n", Dialect::Zsh).unwrap();
+        assert_eq!(d.get_string_field_or_parent("child", "key"), Ok("value".to_string()));
+        assert_eq!("zsh".parse::<Dialect>(), Ok(Dialect::Zsh));
+        let e = "tcsh".parse::<Dialect>().unwrap_err();
+        assert!(matches!(&e, YamlPathError::ParseError { path, .. } if path == "tcsh"));
+        assert_eq!(e.to_string(), "failed to parse tcsh: unknown dialect");
+        assert_eq!(Dialect::from(true), Dialect::Bash);
     }
 
     #[test]
@@ -228,7 +1227,7 @@ mod u_tests {
                  six: 6
                  nine: 9
     ";
-          let descender = YamlDescender::new(&s, true).unwrap() ;
+          let descender = YamlDescender::new(s, true).unwrap() ;
           let x = match descender.yaml_descend_path("tree.sub-array[2].nine").unwrap() {
              Yaml::Integer(i) => i,
              _ => panic!("expected an int")
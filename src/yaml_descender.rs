@@ -21,56 +21,55 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 // 
-use std::collections::HashSet;
-use crate::arrayparser::{ArrayParser, ZshArrayParser};
-use crate::arrayparser::BashArrayParser;
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::sync::OnceLock;
+use crate::arrayparser::{ArrayParser, Dialect};
 use std::io::Write;
 use std::string::String;
-use yaml_rust::{Yaml, YamlLoader};
-use regex::Regex;
+use std::path::{Path, PathBuf};
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
 use crate::descender::Descender;
-use crate::rust_common::{keys_starting_with, sep};
-use crate::yaml_path::yaml_path;
-
-const KEY_MATCH: usize = 1 ;
-const PERIOD_MATCH: usize = 2 ;
-const INDEX_MATCH: usize = 3 ;
+use crate::rust_common::matching_keys;
+use crate::matcher::{MatchMode, Matcher};
+use crate::yaml_path::{all_nodes, descend, descend_mut, query_into, remove_in, set_in, Ancestors, DEFAULT_MAX_PARENT_DEPTH};
+use crate::error::YamlPathError;
+use crate::flatten::{flatten_with, FlattenOptions};
+use crate::provenance::Provenance;
+use crate::merge::{inherited_field, Inheritance};
+use crate::get_metadata::Metadata;
+use crate::completion::{write_fish, write_plain, write_protocol, Completion, NodeKind};
+use crate::include::{expand_includes, load_file_mounted, load_str, rebase, Files, Mounts};
+use crate::path::{PartialPath, PartialTail, Segment, ToYamlPath, YamlPath};
+use crate::hints::split_value;
 
 pub struct YamlDescender {
     docs: Vec<Yaml>,
-    ap: Box<dyn ArrayParser>,
-    root: Yaml,
-    re: Regex,
-    parent_key: Yaml,
+    dialect: Dialect,
+    pub(crate) ap: Box<dyn ArrayParser>,
+    pub(crate) metadata: Metadata,
     description_key: Yaml,
-    terminal_fields: HashSet<Yaml>
+    // the first document holding data rather than only completion-metadata
+    first_document: usize,
+    // the selected document, an index into docs
+    document: usize,
+    across_documents: bool,
+    max_parent_depth: usize,
+    inheritance: Inheritance,
+    // tried in turn until one matches a candidate
+    pub(crate) matchers: Vec<Box<dyn Matcher>>,
+    // other files reached through `parent`
+    files: Files,
+    // node address to path under the root, built on first use
+    path_index: OnceLock<HashMap<usize, YamlPath>>
 }
 
-fn get_string_set(yaml: Yaml) -> Result<HashSet<Yaml>, String> {
-    match yaml {
-        Yaml::Array(a) => { Ok(HashSet::from_iter(a.iter().map(|y| y.clone())))  }
-        _ => Err(String::from("not an array"))
-    }
-}
+/// separates a document qualifier from a path when completing across documents
+pub const DOCUMENT_SEPARATOR: &str = "::";
 
 /// Descends into tree like objects such as yaml or (coming soon) json
 impl YamlDescender {
-    // traditional array accessor "([^.\[\]\\]+)(\.)?|(?:\[(\d+)]?)?"
-    // more zsh friendly "([^.\[\]\\@]+)(\.)?|(?:@(\d+))?"
-
-    fn get_ap(bash_or_zsh: bool) -> Box<dyn ArrayParser> {
-        if bash_or_zsh {
-            Box::new(BashArrayParser::new())
-        }
-        else {
-            Box::new(ZshArrayParser::new())
-        }
-    }
-
-    fn get_parent_key() -> Yaml {
-        Yaml::String("parent".to_string())
-    }
-
     fn get_description_key() -> Yaml {
         Yaml::String("description".to_string())
     }
@@ -81,75 +80,160 @@ impl YamlDescender {
     /// # Arguments
     ///   docstr:
     ///     string as yaml data
-    ///   bash_or_zsh bool
-    ///     - true - arrays are indexed with [index]
-    ///     - false - arrays are indexed as @index
-    ///
-    pub fn new(docstr: &str, bash_or_zsh: bool) -> Result<YamlDescender, String> {
-        let docs = match YamlLoader::load_from_str(docstr) {
-            Ok(d) => d,
-            Err(e) => return Err(format!("failed to parse yaml: {}", e))
-        } ;
+    ///   dialect: [`Dialect`]
+    ///     - Bash - arrays are indexed with [index], `true` converts to this
+    ///     - Zsh - arrays are indexed as @index, `false` converts to this
+    ///     - JsonPointer - RFC 6901 pointers, /key/index
+    ///
+    /// Every document in `docstr` is loaded and the first is selected, see
+    /// [`select_document`](Self::select_document).  A leading document
+    /// holding nothing but `completion-metadata` configures the others and
+    /// is not itself selectable.
+    ///
+    /// No files are read: an `include` key is ordinary data and a `parent`
+    /// naming another file is an error, see
+    /// [`new_with_base`](Self::new_with_base).
+    ///
+    pub fn new(docstr: &str, dialect: impl Into<Dialect>) -> Result<YamlDescender, YamlPathError> {
+        YamlDescender::load_from_str(docstr, dialect.into(), false)
+    }
+
+    ///
+    /// [`new`](Self::new), first checking `completion-metadata` with
+    /// [`Metadata::validate`].  Any problem fails with a
+    /// [`YamlPathError::InvalidMetadata`] holding all of them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::error::YamlPathError;
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = "completion-metadata: {root: data, terminal-feilds: [bits]}\ndata: {a: 1}" ;
+    /// assert!(YamlDescender::new(s, true).is_ok()) ;
+    /// match YamlDescender::new_strict(s, true) {
+    ///     Err(YamlPathError::InvalidMetadata { problems, .. }) => assert_eq!(problems[0].segment(), "terminal-feilds"),
+    ///     _ => panic!("expected InvalidMetadata")
+    /// }
+    /// ```
+    ///
+    pub fn new_strict(docstr: &str, dialect: impl Into<Dialect>) -> Result<YamlDescender, YamlPathError> {
+        YamlDescender::load_from_str(docstr, dialect.into(), true)
+    }
 
-        let root = yaml_path(&docs[0], "completion-metadata.root").unwrap_or_else(|_| Yaml::String("".to_string()));
+    fn load_from_str(docstr: &str, dialect: Dialect, strict: bool) -> Result<YamlDescender, YamlPathError> {
+        YamlDescender::from_documents(load_str(docstr, "")?, dialect, None, strict)
+    }
 
-        let terminal_fields = match yaml_path(&docs[0], "completion-metadata.terminal-fields") {
-            Ok(y) => match get_string_set(y) {
-                Ok(s) => s,
-                Err(s) => {return Err(format!("terminal-field: {}", s))}
+    ///
+    /// Create a descender from a string read as if from a file in `base`.
+    /// Unlike [`new`](Self::new), which never reads files and keeps an
+    /// `include` key as data, `include` mounts files and `parent` may name
+    /// other files, both found from `base`, see [`crate::include::load_file`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::descender::Descender;
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let dir = std::env::temp_dir().join("aep-new-with-base-doc");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("pins.yaml"), "set0: {width: 32}\n").unwrap();
+    /// let s = "GPIO:\n  include: pins.yaml\n" ;
+    /// let descender = YamlDescender::new_with_base(s, true, &dir).unwrap() ;
+    /// assert_eq!(descender.get_int_field_or_parent("GPIO.set0", "width"), Ok(32)) ;
+    /// let plain = YamlDescender::new(s, true).unwrap() ;
+    /// assert_eq!(plain.yaml_descend_path("GPIO.include").unwrap().as_str(), Some("pins.yaml")) ;
+    /// ```
+    ///
+    pub fn new_with_base(docstr: &str, dialect: impl Into<Dialect>, base: impl AsRef<Path>) -> Result<YamlDescender, YamlPathError> {
+        let base = base.as_ref();
+        let mut docs = load_str(docstr, "")?;
+        let mut mounts = Vec::new();
+        for doc in docs.iter_mut() {
+            let mut from = vec![(YamlPath::new(), base.to_path_buf())];
+            expand_includes(doc, base, "", &mut Vec::new(), &mut Vec::new(), &mut from)?;
+            mounts.push(from);
+        }
+        YamlDescender::from_documents(docs, dialect.into(), Some((base.to_path_buf(), mounts)), false)
+    }
+
+    // `base` is the directory other files are found from, with where the
+    // parts of each document came from, `None` reading no files
+    fn from_documents(docs: Vec<Yaml>, dialect: Dialect, base: Option<(PathBuf, Vec<Mounts>)>, strict: bool) -> Result<YamlDescender, YamlPathError> {
+        if docs.is_empty() {
+            return Err(YamlPathError::ParseError { path: String::new(), segment: String::new(), message: String::from("no YAML documents") });
+        }
+        if strict {
+            let problems = Metadata::validate(&docs);
+            if !problems.is_empty() {
+                return Err(YamlPathError::InvalidMetadata { path: String::from("completion-metadata"), segment: String::new(), problems });
             }
-            Err(_) => { HashSet::new() }
+        }
+        let first_document = match docs[0].as_hash() {
+            Some(h) if docs.len() > 1 && h.len() == 1 && h.contains_key(&Yaml::String(String::from("completion-metadata"))) => 1,
+            _ => 0
         } ;
 
-        let parent_key = Self::get_parent_key();
-        let ap = Self::get_ap(bash_or_zsh) ;
+        let metadata = Metadata::from_yaml(&docs[0])?;
 
         Ok(YamlDescender {
-            docs,
-            re: ap.get_re(),
-            parent_key,
-            root,
+            dialect,
+            metadata,
             description_key: Self::get_description_key(),
-            terminal_fields,
-            ap
+            ap: dialect.array_parser(),
+            first_document,
+            document: first_document,
+            across_documents: docs.len() - first_document > 1,
+            max_parent_depth: DEFAULT_MAX_PARENT_DEPTH,
+            inheritance: Inheritance::FirstFound,
+            matchers: MatchMode::default().matchers(),
+            files: base.map(|(base, mounts)| Files::load(base, &docs, mounts)).unwrap_or_default(),
+            docs,
+            path_index: OnceLock::new()
         })
     }
 
     ///
-    /// Create a descender from a file
+    /// Create a descender from a file, `include` and other files named by
+    /// `parent` being found from the file's directory, see
+    /// [`crate::include::load_file`]
     ///
-    pub fn new_from_file(path: &str, bash_or_zsh: bool) -> Result<YamlDescender, String> {
-        let docstr = match std::fs::read_to_string(path) {
-            Ok(d) => d,
-            Err(e) => return Err(format!("failed to read file: {}", e))
-        } ;
-        YamlDescender::new(&docstr, bash_or_zsh)
+    pub fn new_from_file(path: &str, dialect: impl Into<Dialect>) -> Result<YamlDescender, YamlPathError> {
+        YamlDescender::load_from_file(path, dialect.into(), false)
+    }
+
+    /// [`new_from_file`](Self::new_from_file) checking the metadata as
+    /// [`new_strict`](Self::new_strict) does
+    pub fn new_from_file_strict(path: &str, dialect: impl Into<Dialect>) -> Result<YamlDescender, YamlPathError> {
+        YamlDescender::load_from_file(path, dialect.into(), true)
     }
 
-    pub fn new_from_yaml(yaml: &Yaml, bash_or_zsh : bool ) -> Result<YamlDescender, String> {
-        let ap = Self::get_ap(bash_or_zsh) ;
+    fn load_from_file(path: &str, dialect: Dialect, strict: bool) -> Result<YamlDescender, YamlPathError> {
+        let (docs, mounts) = load_file_mounted(path)?.into_iter().unzip();
+        let base = Path::new(path).canonicalize().ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        YamlDescender::from_documents(docs, dialect, Some((base, mounts)), strict)
+    }
+
+    pub fn new_from_yaml(yaml: &Yaml, dialect: impl Into<Dialect>) -> Result<YamlDescender, YamlPathError> {
+        let dialect = dialect.into();
         match yaml {
-            Yaml::Hash(_h) => {
+            Yaml::Hash(_) | Yaml::Array(_) => {
                 Ok(YamlDescender { docs: vec![yaml.clone()],
-                    re: ap.get_re(),
-                    parent_key: Self::get_parent_key(),
-                    root: Yaml::String("".to_string()),
+                    dialect,
+                    metadata: Metadata::default(),
                     description_key: Self::get_description_key(),
-                    terminal_fields: HashSet::new(),
-                    ap: ap
+                    ap: dialect.array_parser(),
+                    first_document: 0,
+                    document: 0,
+                    across_documents: false,
+                    max_parent_depth: DEFAULT_MAX_PARENT_DEPTH,
+                    inheritance: Inheritance::FirstFound,
+                    matchers: MatchMode::default().matchers(),
+                    files: Files::default(),
+                    path_index: OnceLock::new()
                 })
             }
-            Yaml::Array(_a) => {
-                Ok(YamlDescender { docs: vec![yaml.clone()],
-                    re: ap.get_re(),
-                    parent_key: Self::get_parent_key(),
-                    root: Yaml::String("".to_string()),
-                    description_key: Self::get_description_key(),
-                    terminal_fields: HashSet::new(),
-                    ap: ap
-                },)
-            }
-            _ => { return Err(String::from("cannot create from scalar types"))}
+            _ => Err(YamlPathError::TypeMismatch { path: String::new(), segment: String::new(), expected: "a hash or an array" })
         }
     }
 
@@ -178,56 +262,317 @@ impl YamlDescender {
     ///
     /// ```
     ///
-    pub fn yaml_descend_path(&self, path: &str) -> Result<&Yaml, String> {
-        let re = &self.re ;
-        let mut current = &self.docs[0];
-        if path == "" {
-            return Ok(current);
+    pub fn yaml_descend_path<P: ToYamlPath + ?Sized>(&self, path: &P) -> Result<&Yaml, YamlPathError> {
+        let (doc, path) = self.qualified(path)?;
+        self.descend_in(doc, &path)
+    }
+
+    ///
+    /// The document a path is in and the path within it.  With more than
+    /// one document, text starting `name::` or `index::`, as completion
+    /// qualifies paths, is in that document, anything else in the selected
+    /// one.
+    ///
+    fn qualified<'p, P: ToYamlPath + ?Sized>(&self, path: &'p P) -> Result<(&Yaml, std::borrow::Cow<'p, YamlPath>), YamlPathError> {
+        if self.document_count() > 1
+            && let Some((qualifier, rest)) = path.as_text().and_then(|t| t.split_once(DOCUMENT_SEPARATOR))
+            && let Some(index) = self.find_document(qualifier, true) {
+            let rest = self.ap.parse_path(rest)?;
+            return Ok((&self.docs[self.first_document + index], std::borrow::Cow::Owned(rest)));
         }
+        Ok((self.document(), path.to_yaml_path(self.ap.as_ref())?))
+    }
 
-        if self.root.as_str().unwrap_or("") != "" {
-            match current {
-                Yaml::Hash(h) => {
-                    let ykey = Yaml::String(self.root.as_str().unwrap().to_string());
-                    if !h.contains_key(&ykey) {
-                        return Err(format!("{} not found in {}", self.root.as_str().unwrap(), path));
-                    }
-                    current = &h[&ykey];
+    ///
+    /// [`get_field_or_parent`](Self::get_field_or_parent) of the node at
+    /// `path`, which may be qualified by its document.  A missing field or
+    /// a node that is not a hash is reported at `path`, a cycle or an over
+    /// deep chain keeps the chain as its path.
+    ///
+    fn field_at(&self, path: &str, field: &str) -> Result<Yaml, YamlPathError> {
+        let (doc, parsed) = self.qualified(path)?;
+        let child = self.descend_in(doc, &parsed)?;
+        self.field_in(doc, child, field).map_err(|e| match e {
+            YamlPathError::KeyNotFound { .. } => e.with_path(path),
+            YamlPathError::NotAHash { .. } => YamlPathError::NotAHash { path: path.to_string(), segment: String::new() },
+            e => e,
+        })
+    }
+
+    /// `path` under the root of `doc`, the empty path being `doc` itself
+    fn descend_in<'a>(&self, doc: &'a Yaml, path: &YamlPath) -> Result<&'a Yaml, YamlPathError> {
+        if path.is_empty() {
+            return Ok(doc);
+        }
+        descend(self.root_in(doc)?, path, self.ap.as_ref())
+    }
+
+    ///
+    /// A mutable reference to the node at `path` under the configured root
+    ///
+    pub fn yaml_path_mut<P: ToYamlPath + ?Sized>(&mut self, path: &P) -> Result<&mut Yaml, YamlPathError> {
+        let path = path.to_yaml_path(self.ap.as_ref())?;
+        self.invalidate_paths();
+        let ap = self.dialect.array_parser();
+        descend_mut(self.root_node_mut()?, &path, ap.as_ref())
+    }
+
+    ///
+    /// Set the node at `path` under the configured root to `value`, see
+    /// [`crate::yaml_path::set_at_path`] for how `create_missing` works.
+    ///
+    /// # Example
+    /// ```rust
+    /// use yaml_rust::Yaml;
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// completion-metadata:
+    ///   root: data
+    /// data:
+    ///   pins: []
+    /// " ;
+    /// let mut descender = YamlDescender::new(s, false).unwrap() ;
+    /// descender.set_at_path("pins@0.level", Yaml::Integer(1), true).unwrap() ;
+    /// assert_eq!(descender.yaml_descend_path("pins@0.level").unwrap(), &Yaml::Integer(1)) ;
+    /// ```
+    ///
+    pub fn set_at_path<P: ToYamlPath + ?Sized>(&mut self, path: &P, value: Yaml, create_missing: bool) -> Result<Option<Yaml>, YamlPathError> {
+        let path = path.to_yaml_path(self.ap.as_ref())?;
+        self.invalidate_paths();
+        let ap = self.dialect.array_parser();
+        set_in(self.root_node_mut()?, &path, value, create_missing, ap.as_ref())
+    }
+
+    ///
+    /// Remove the node at `path` under the configured root and return it
+    ///
+    pub fn remove_at_path<P: ToYamlPath + ?Sized>(&mut self, path: &P) -> Result<Yaml, YamlPathError> {
+        let path = path.to_yaml_path(self.ap.as_ref())?;
+        self.invalidate_paths();
+        let ap = self.dialect.array_parser();
+        remove_in(self.root_node_mut()?, &path, ap.as_ref())
+    }
+
+    // forget the paths of nodes, called by everything that changes the
+    // tree, the root or the selected document
+    fn invalidate_paths(&mut self) {
+        self.path_index = OnceLock::new();
+    }
+
+    /// the selected document, including any changes made through this descender
+    pub fn document(&self) -> &Yaml {
+        &self.docs[self.document]
+    }
+
+    /// the number of selectable documents
+    pub fn document_count(&self) -> usize {
+        self.docs.len() - self.first_document
+    }
+
+    /// the index of the selected document
+    pub fn selected_document(&self) -> usize {
+        self.document - self.first_document
+    }
+
+    /// the `name` field of a document, if it has one
+    pub fn document_name(&self, index: usize) -> Option<&str> {
+        self.docs.get(self.first_document + index)?.as_hash()?
+            .get(&Yaml::String(String::from("name")))?.as_str()
+    }
+
+    ///
+    /// Select the document that paths are resolved in, counting from zero
+    /// and skipping a leading `completion-metadata` document.
+    ///
+    pub fn select_document(&mut self, index: usize) -> Result<(), YamlPathError> {
+        if index >= self.document_count() {
+            return Err(YamlPathError::IndexOutOfBounds { path: String::new(), segment: index.to_string() });
+        }
+        self.document = self.first_document + index;
+        self.invalidate_paths();
+        Ok(())
+    }
+
+    ///
+    /// Select the first document whose top level `name` field is `name`
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// completion-metadata:
+    ///   root: data
+    /// ---
+    /// name: uart
+    /// data:
+    ///   baud: 9600
+    /// ---
+    /// name: spi
+    /// data:
+    ///   mode: 3
+    /// " ;
+    /// let mut descender = YamlDescender::new(s, true).unwrap() ;
+    /// assert_eq!(descender.document_count(), 2) ;
+    /// descender.select_document_named("spi").unwrap() ;
+    /// assert_eq!(descender.selected_document(), 1) ;
+    /// assert!(descender.yaml_descend_path("mode").is_ok()) ;
+    /// ```
+    ///
+    pub fn select_document_named(&mut self, name: &str) -> Result<(), YamlPathError> {
+        match self.find_document(name, false) {
+            Some(index) => self.select_document(index),
+            None => Err(YamlPathError::KeyNotFound { path: String::new(), segment: name.to_string() })
+        }
+    }
+
+    /// a document by name or, if `by_index`, by number
+    fn find_document(&self, qualifier: &str, by_index: bool) -> Option<usize> {
+        (0..self.document_count()).find(|i| self.document_name(*i) == Some(qualifier))
+            .or_else(|| qualifier.parse::<usize>().ok().filter(|i| by_index && *i < self.document_count()))
+    }
+
+    /// how completions name a document, its `name` or else its number
+    fn document_qualifier(&self, index: usize) -> String {
+        self.document_name(index).map(str::to_string).unwrap_or_else(|| index.to_string())
+    }
+
+    ///
+    /// Whether completions cover every document, each qualified with its
+    /// `name` or number and [`DOCUMENT_SEPARATOR`], `spi::mode`.  This is on
+    /// by default when there is more than one document.
+    ///
+    pub fn complete_across_documents(&mut self, across: bool) {
+        self.across_documents = across;
+    }
+
+    ///
+    /// Find every node under the configured root matching a path that may
+    /// contain `*` and `**` wildcards, see [`crate::yaml_path::yaml_query`].
+    /// Paths in the result are relative to the root and in this descender's
+    /// dialect when formatted with [`YamlPath::to_string_with`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// completion-metadata:
+    ///   root: data
+    /// data:
+    ///   pins:
+    ///     - level: 1
+    ///     - level: 0
+    /// " ;
+    /// let descender = YamlDescender::new(s, false).unwrap() ;
+    /// let levels = descender.yaml_query("pins.*.level").unwrap() ;
+    /// assert_eq!(levels.len(), 2) ;
+    /// assert_eq!(descender.format_path(&levels[1].0), "pins@1.level") ;
+    /// ```
+    ///
+    pub fn yaml_query<P: ToYamlPath + ?Sized>(&self, path: &P) -> Result<Vec<(YamlPath, &Yaml)>, YamlPathError> {
+        let path = path.to_yaml_path(self.ap.as_ref())?;
+        let mut out = Vec::new();
+        query_into(self.root_node()?, path.segments(), &mut Vec::new(), &mut out, &|node, field| self.field_lookup(self.document(), node, field));
+        Ok(out)
+    }
+
+    /// a field of `node` in `doc` for filter predicates, inherited fields included
+    fn field_lookup(&self, doc: &Yaml, node: &Yaml, field: &str) -> Option<Yaml> {
+        self.field_in(doc, node, field).ok()
+    }
+
+    ///
+    /// The path of `node` under the configured root in this descender's
+    /// dialect.  `node` must be a reference into this descender, as
+    /// returned by [`yaml_descend_path`](Self::yaml_descend_path),
+    /// [`yaml_query`](Self::yaml_query) or [`paths`](Self::paths), copies
+    /// such as inherited field values have no path.  The root itself is
+    /// the empty path.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// completion-metadata:
+    ///   root: data
+    /// data:
+    ///   pins:
+    ///     - level: 1
+    /// " ;
+    /// let descender = YamlDescender::new(s, false).unwrap() ;
+    /// let node = descender.yaml_descend_path("pins@0.level").unwrap() ;
+    /// assert_eq!(descender.path_of(node).unwrap(), "pins@0.level") ;
+    /// ```
+    ///
+    pub fn path_of(&self, node: &Yaml) -> Option<String> {
+        let index = self.path_index.get_or_init(|| {
+            match self.root_node() {
+                Ok(root) => all_nodes(root).into_iter()
+                    .map(|(path, node)| (node as *const Yaml as usize, path))
+                    .collect(),
+                Err(_) => HashMap::new(),
+            }
+        });
+        index.get(&(node as *const Yaml as usize)).map(|path| self.ap.format_path(path))
+    }
+
+    ///
+    /// Every node under the configured root, parents before their members,
+    /// with its path in this descender's dialect.  The root itself is not
+    /// included.
+    ///
+    pub fn paths(&self) -> impl Iterator<Item = (String, &Yaml)> + '_ {
+        let nodes = match self.root_node() {
+            Ok(root) => all_nodes(root),
+            Err(_) => Vec::new(),
+        };
+        nodes.into_iter().skip(1).map(|(path, node)| (self.ap.format_path(&path), node))
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+
+    /// format `path` in this descender's dialect
+    pub fn format_path(&self, path: &YamlPath) -> String {
+        self.ap.format_path(path)
+    }
+
+    /// the node named by `completion-metadata.root`, or the document itself
+    fn root_node(&self) -> Result<&Yaml, YamlPathError> {
+        self.root_in(self.document())
+    }
+
+    fn root_in<'a>(&self, current: &'a Yaml) -> Result<&'a Yaml, YamlPathError> {
+        let root = self.metadata.root.as_str();
+        if root.is_empty() {
+            return Ok(current);
+        }
+        match current {
+            Yaml::Hash(h) => {
+                match h.get(&Yaml::String(root.to_string())) {
+                    Some(y) => Ok(y),
+                    None => Err(YamlPathError::KeyNotFound { path: String::new(), segment: root.to_string() })
                 }
-                _ => return Err(format!("{} in {} is not a hash", self.root.as_str().unwrap(), path))
             }
+            _ => Err(YamlPathError::NotAHash { path: String::new(), segment: root.to_string() })
         }
+    }
 
-        for captures in re.captures_iter(path) {
-            if captures.get(KEY_MATCH).is_some() {
-                let key = captures.get(KEY_MATCH).unwrap().as_str(); 
-                match current {
-                    Yaml::Hash(h) => {
-                        let ykey = Yaml::String(key.to_string());
-                        if !h.contains_key(&ykey) {
-                            return Err(format!("{} not found", key));
-                        }
-                        current = &h[&ykey];
-                    }
-                    _ => return Err(format!("{} in {} is not a hash", key, path))
-                } // if str
-            } else if captures.get(INDEX_MATCH).is_some() {
-                let index = captures.get(INDEX_MATCH).unwrap().as_str().parse::<usize>().unwrap();
-                match current {
-                    Yaml::Array(a) => {
-                        if index >= a.len() {
-                            return Err(format!("{} is out of bounds in {}", index, path));
-                        }
-                        current = &a[index];
-                    }
-                    _ => return Err(format!("{} {} is not an array", path, index))
+    /// the mutable counterpart of `root_node`
+    fn root_node_mut(&mut self) -> Result<&mut Yaml, YamlPathError> {
+        let root = self.metadata.root.as_str();
+        if root.is_empty() {
+            return Ok(&mut self.docs[self.document]);
+        }
+        match &mut self.docs[self.document] {
+            Yaml::Hash(h) => {
+                match h.get_mut(&Yaml::String(root.to_string())) {
+                    Some(y) => Ok(y),
+                    None => Err(YamlPathError::KeyNotFound { path: String::new(), segment: root.to_string() })
                 }
-            } // if index
-            else {
-                return Err(format!("{} is not a valid path", path))
             }
-        } // for
-        Ok(current)
+            _ => Err(YamlPathError::NotAHash { path: String::new(), segment: root.to_string() })
+        }
     }
 
     ///
@@ -236,6 +581,12 @@ impl YamlDescender {
     /// document specified by a path and check for the field there.
     /// This is done recursively.
     ///
+    /// `parent` may also be a list of paths.  Ancestors are then searched
+    /// depth first, left to right: the node itself, its first parent and
+    /// all of that parent's ancestors, then its second parent and its
+    /// ancestors and so on, the first hash holding the field wins unless
+    /// [`set_inheritance`](Self::set_inheritance) asks for a deep merge.
+    ///
     /// # Example
     /// ```rust
     /// let s = r"tree:
@@ -255,280 +606,454 @@ impl YamlDescender {
     /// ```
     ///
     ///
-    pub fn get_field_or_parent(&self, child: &Yaml, field: &str) -> Result<Yaml, String> {
+    pub fn get_field_or_parent(&self, child: &Yaml, field: &str) -> Result<Yaml, YamlPathError> {
+        self.field_in(self.document(), child, field)
+    }
 
-        let h = match child {
-            Yaml::Hash(h) => h,
-            _ => return Err(format!("{} is not a hash", field))
-        } ;
-        let field_key = Yaml::String(String::from(field));
+    /// `get_field_or_parent` with parents resolved in `doc`
+    fn field_in(&self, doc: &Yaml, child: &Yaml, field: &str) -> Result<Yaml, YamlPathError> {
+        if child.as_hash().is_none() {
+            return Err(YamlPathError::NotAHash { path: String::new(), segment: field.to_string() });
+        }
+        match inherited_field(&self.ancestors_in(doc)?, child, field, self.inheritance)? {
+            Some(value) => Ok(value),
+            None => Err(YamlPathError::KeyNotFound { path: String::new(), segment: field.to_string() })
+        }
+    }
 
-        // let keyvector = h.keys().collect::<Vec<&Yaml>>();
-        if h.contains_key(&field_key) {
-            return Ok(h[&field_key].clone());
-        } else if h.contains_key(&self.parent_key) {
-            let parent_path = match &h[&self.parent_key] {
-                Yaml::String(s) => s.as_str(),
-                _ => return Err("parent is not a string".parse().unwrap())
-            } ;
-            let parent= match self.yaml_descend_path(parent_path) {
-                Ok(yaml) => yaml,
-                Err(e) => return Err(e)
-            } ;
-            return self.get_field_or_parent(parent, field);
+    ///
+    /// [`get_field_or_parent`](Self::get_field_or_parent) for the node at
+    /// `path`, also reporting every node searched and the one where the
+    /// field was found.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// words:
+    ///   function0:
+    ///     description: pin function
+    /// pins:
+    ///   - function:
+    ///       parent: words.function0
+    /// " ;
+    /// let descender = YamlDescender::new(s, true).unwrap() ;
+    /// let p = descender.get_field_with_provenance("pins[0].function", "description").unwrap() ;
+    /// assert_eq!(p.found_at, "words.function0") ;
+    /// assert_eq!(p.visited, vec!["pins[0].function", "words.function0"]) ;
+    /// assert_eq!(p.explain(), "description of pins[0].function is pin function
+    ///   pins[0].function: not set
+    ///   words.function0: found
+    /// ") ;
+    /// ```
+    ///
+    pub fn get_field_with_provenance<P: ToYamlPath + ?Sized>(&self, path: &P, field: &str) -> Result<Provenance, YamlPathError> {
+        let path = path.to_yaml_path(self.ap.as_ref())?.into_owned();
+        let formatted = self.ap.format_path(&path);
+        let child = self.yaml_descend_path(&path)?;
+        if child.as_hash().is_none() {
+            return Err(YamlPathError::NotAHash { path: formatted, segment: field.to_string() });
+        }
+        let field_key = Yaml::String(String::from(field));
+        let visited = RefCell::new(Vec::new());
+        let found = self.ancestors_in(self.document())?.search_at(child, path, &|h, holder| {
+            let holder = holder.map(|p| self.ap.format_path(p)).unwrap_or_default();
+            visited.borrow_mut().push(holder.clone());
+            Ok(h.get(&field_key).map(|v| (v.clone(), holder)))
+        })?;
+        match found {
+            Some((value, found_at)) => Ok(Provenance { path: formatted, field: field.to_string(), value, visited: visited.into_inner(), found_at }),
+            None => Err(YamlPathError::KeyNotFound { path: formatted, segment: field.to_string() })
         }
-        Err(format!("field {} not found", field))
     }
 
-    fn has_terminal_field(&self, yaml: &Yaml) -> bool {
-        let h = match yaml.as_hash() {
-            Some(h) => h,
-            None => return false
-        } ;
+    /// resolves `parent` references under the root of `doc`
+    fn ancestors_in<'a>(&'a self, doc: &'a Yaml) -> Result<Ancestors<'a, 'a>, YamlPathError> {
+        let root = self.root_in(doc)?;
+        let under = match self.metadata.root.as_str() {
+            "" => YamlPath::new(),
+            key => YamlPath::from(vec![Segment::Key(key.to_string())]),
+        };
+        let mounts = self.docs.iter().position(|d| std::ptr::eq(d, doc))
+            .and_then(|index| self.files.main_mounts(index))
+            .map(|mounts| rebase(mounts, &under))
+            .unwrap_or_default();
+        Ok(Ancestors { root, ap: self.ap.as_ref(), max_depth: self.max_parent_depth, files: Some(&self.files), mounts })
+    }
 
-        for k in h.keys() {
-            if self.terminal_fields.contains(k) {
-                return true;
-            }
-        }
-        false
+    ///
+    /// A copy of the tree under the configured root of the selected
+    /// document with every inherited field written into the hash that
+    /// inherits it, see [`crate::flatten::flatten`]
+    ///
+    /// # Example
+    /// ```rust
+    /// use yaml_rust::Yaml;
+    /// use aep_rust_common::flatten::FlattenOptions;
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// completion-metadata:
+    ///   root: data
+    /// data:
+    ///   words:
+    ///     set0:
+    ///       width: 32
+    ///   pins:
+    ///     - set:
+    ///         parent: ...words.set0
+    /// " ;
+    /// let descender = YamlDescender::new(s, true).unwrap() ;
+    /// let flat = descender.flattened(FlattenOptions::default()).unwrap() ;
+    /// assert_eq!(flat["pins"][0]["set"]["width"], Yaml::Integer(32)) ;
+    /// ```
+    ///
+    pub fn flattened(&self, options: FlattenOptions) -> Result<Yaml, YamlPathError> {
+        let ancestors = self.ancestors_in(self.document())?;
+        flatten_with(&ancestors, ancestors.root, YamlPath::new(), options)
     }
-}
 
-impl Descender<dyn Write> for YamlDescender {
+    ///
+    /// Choose how a field found on several nodes of a `parent` chain is
+    /// resolved by [`get_field_or_parent`](Self::get_field_or_parent) and
+    /// the `get_*_field_or_parent` methods, first found unless set.
+    ///
+    /// # Example
+    /// ```rust
+    /// use yaml_rust::Yaml;
+    /// use aep_rust_common::merge::{ArrayMerge, Inheritance};
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// base:
+    ///   access: {read: true, write: true}
+    /// ro:
+    ///   parent: base
+    ///   access: {write: false}
+    /// " ;
+    /// let mut descender = YamlDescender::new(s, true).unwrap() ;
+    /// descender.set_inheritance(Inheritance::DeepMerge(ArrayMerge::Replace)) ;
+    /// let access = descender.get_field_or_parent(descender.yaml_descend_path("ro").unwrap(), "access").unwrap() ;
+    /// assert_eq!(access["read"], Yaml::Boolean(true)) ;
+    /// assert_eq!(access["write"], Yaml::Boolean(false)) ;
+    /// ```
+    ///
+    pub fn set_inheritance(&mut self, inheritance: Inheritance) {
+        self.inheritance = inheritance;
+    }
 
-    fn set_root(&mut self, path: &str) -> Result<String, String> {
-        let old_root = self.root.as_str().unwrap().to_string();
-        self.root = Yaml::String(path.to_string());
-        Ok(old_root) 
+    ///
+    /// How keys and hinted values are matched against what has been typed,
+    /// [`MatchMode::Fallback`] unless set.  `MatchMode::Prefix` only offers
+    /// exact prefixes.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// use aep_rust_common::matcher::MatchMode;
+    /// let s = r"---
+    /// GPIO: {base: 0}
+    /// TIMER: {base: 1}
+    /// " ;
+    /// let mut descender = YamlDescender::new(s, true).unwrap() ;
+    /// assert_eq!(descender.completions("gpio")[0].text, "GPIO.base") ;
+    /// descender.set_match_mode(MatchMode::Prefix) ;
+    /// assert!(descender.completions("gpio").is_empty()) ;
+    /// ```
+    ///
+    pub fn set_match_mode(&mut self, mode: MatchMode) {
+        self.matchers = mode.matchers();
     }
 
-    fn get_string_field_or_parent(&self, path: &str, field: &str) -> Result<String, String> {
-        let child = match self.yaml_descend_path(path) {
-            Ok(yaml) => yaml,
-            Err(e) => return Err(e)
-        } ;
-        let value = self.get_field_or_parent(&child, field) ;
-        match value {
-            Ok(v) => match v {
-                Yaml::String(s) => Ok(s),
-                _ => Err(format!("{}.{} is not a string", path, field))
-            }
-            _ => Err(format!("{}.{} not found", path, field))
+    /// Match with `matchers`, tried in turn until one matches a candidate
+    pub fn set_matchers(&mut self, matchers: Vec<Box<dyn Matcher>>) {
+        self.matchers = matchers;
+    }
+
+    ///
+    /// Limit how many `parent` links a lookup follows,
+    /// [`DEFAULT_MAX_PARENT_DEPTH`] unless set.  A longer chain is a
+    /// [`YamlPathError::ParentDepthExceeded`].
+    ///
+    pub fn set_max_parent_depth(&mut self, depth: usize) {
+        self.max_parent_depth = depth;
+    }
+
+    ///
+    /// Check every `parent` chain under the configured root of the selected
+    /// document for cycles and for chains longer than the maximum depth.
+    /// Each problem is reported once, a cycle as a
+    /// [`YamlPathError::ParentCycle`] naming its chain, `a -> b -> a`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// a:
+    ///   parent: b
+    /// b:
+    ///   parent: a
+    /// " ;
+    /// let descender = YamlDescender::new(s, true).unwrap() ;
+    /// let cycles = descender.parent_cycles() ;
+    /// assert_eq!(cycles[0].path(), "a -> b -> a") ;
+    /// ```
+    ///
+    pub fn parent_cycles(&self) -> Vec<YamlPathError> {
+        match self.ancestors_in(self.document()) {
+            Ok(ancestors) => ancestors.problems(),
+            Err(_) => Vec::new()
         }
     }
 
-    fn get_int_field_or_parent(&self, path: &str, field: &str) -> Result<i64, String> {
-        let child = match self.yaml_descend_path(path) {
-            Ok(yaml) => yaml,
-            Err(_) => return Err(format!("{}.{} not found", path, field))
-        };
-        let value_r = self.get_field_or_parent(&child, field) ;
-        match value_r {
-            Ok(v) => match v {
-                Yaml::Integer(i) => Ok(i),
-                _ => Err(format!("{}.{} is not an integer", path, field))
+    ///
+    /// Resolve the complete segments of `partial`, which may select several
+    /// nodes through wildcards or slices, then offer the members of each
+    /// node reached that match its tail.  The path starts at the node `below`
+    /// the root, the root itself when `below` is empty.
+    ///
+    pub(crate) fn complete(&self, doc: &Yaml, below: &YamlPath, qualifier: &str, partial: &PartialPath,
+                           add_descriptions: bool) -> Result<Vec<Completion>, YamlPathError> {
+        let root = self.root_in(doc)?;
+        let root = if below.is_empty() { root } else { descend(root, below, self.ap.as_ref())? };
+
+        let mut frontier = Vec::new() ;
+        query_into(root, partial.path.segments(), &mut Vec::new(), &mut frontier, &|node, field| self.field_lookup(doc, node, field)) ;
+
+        let mut candidates = Vec::new() ;
+        for (concrete, node) in frontier {
+            let mut current_path = format!("{}{}", qualifier, self.ap.format_path(&concrete)) ;
+            if !concrete.is_empty() && !self.has_terminal_field(node) {
+                current_path += self.ap.separator(node);
             }
-            Err(e) => Err(e)
+            self.complete_node(doc, node, current_path, &partial.tail, add_descriptions, &mut candidates) ;
         }
+        Ok(candidates)
     }
 
-    fn get_bool_field_or_parent(&self, path: &str, field: &str) -> Result<bool, String> {
-        let child = self.yaml_descend_path(path).unwrap();
-        let value_r = self.get_field_or_parent(&child, field) ;
-         match value_r {
-            Ok(v) => match v {
-                Yaml::Boolean(b) => Ok(b),
-                _ => Err(format!("{}.{} is not a bool", path, field))
+    ///
+    /// Offer the members of `current` matching `tail`, descending
+    /// automatically while there is only one choice.
+    ///
+    fn complete_node(&self, doc: &Yaml, mut current: &Yaml, mut current_path: String, tail: &PartialTail, add_descriptions: bool,
+                     out: &mut Vec<Completion>) {
+        let ap = &self.ap ;
+        let description = |node: &Yaml| if add_descriptions { self.description_in(doc, node).ok() } else { None } ;
+        let mut display = current_path.clone() ;
+
+        // an opened index can only continue into an array
+        let (mut prefix, mut index_pending) = match tail {
+            PartialTail::Key(k) => (k.as_str(), false),
+            PartialTail::Index => ("", true)
+        } ;
+
+        loop {
+            if self.has_terminal_field(current) {
+                break ;
+            }
+            match current {
+                Yaml::Hash(hash) => {
+                    if index_pending { return; }
+                    let keys = matching_keys(prefix, hash, &self.metadata.ignore_fields, &self.matchers);
+                    if keys.is_empty() { return; }
+
+                    if keys.len() == 1 {
+                        let ykey = keys[0];
+                        current = &hash[ykey];
+                        current_path += &ap.format_key(ykey.as_str().unwrap());
+                        display = ykey.as_str().unwrap().to_string();
+                        if !self.has_terminal_field(current) {
+                            current_path += ap.separator(current);
+                        }
+                        prefix = "";
+                        continue;
+                    }
+
+                    for key in keys {
+                        let node = &hash[key];
+                        let text = format!("{}{}", current_path, ap.format_key(key.as_str().unwrap()));
+                        out.push(Completion::node(text, key.as_str().unwrap().to_string(), description(node), node, self.has_terminal_field(node)));
+                    }
+                    return;
+                }
+                Yaml::Array(array) => {
+                    index_pending = false;
+                    // only JSON Pointers type indexes as a prefix
+                    let indexes: Vec<usize> = (0..array.len()).filter(|i| i.to_string().starts_with(prefix)).collect();
+                    if indexes.len() == 1 {
+                        current_path += &ap.apply_index(indexes[0]);
+                        display = ap.apply_index(indexes[0]);
+                        current = &array[indexes[0]];
+                        if !self.has_terminal_field(current) {
+                            current_path += ap.separator(current);
+                        }
+                        prefix = "";
+                        continue;
+                    }
+                    for index in indexes {
+                        let node = &array[index];
+                        let text = format!("{}{}", current_path, ap.apply_index(index));
+                        out.push(Completion::node(text, ap.apply_index(index), None, node, self.has_terminal_field(node)));
+                    }
+                    return;
+                }
+                _ => { break; }
             }
-            Err(e) => Err(e)
         }
+        out.push(Completion::node(current_path, display, None, current, true));
     }
 
-    fn get_float_field_or_parent(&self, path: &str, field: &str) -> Result<f64, String> {
-        let child = self.yaml_descend_path(path).unwrap();
-        let value_r = self.get_field_or_parent(&child, field) ;
+    /// `get_description` with parents resolved in `doc`
+    fn description_in(&self, doc: &Yaml, yaml: &Yaml) -> Result<String, YamlPathError> {
+        if yaml.as_hash().is_none() {
+            return Err(YamlPathError::NotAHash { path: String::new(), segment: String::from("description") });
+        }
+        let description = |h: &Hash, _: Option<&YamlPath>| match h.get(&self.description_key) {
+            None => Ok(None),
+            Some(Yaml::String(s)) => Ok(Some(s.to_string())),
+            Some(_) => Err(YamlPathError::TypeMismatch { path: String::new(), segment: String::from("description"), expected: "a string" })
+        } ;
+        match self.ancestors_in(doc)?.search(yaml, &description)? {
+            Some(s) => Ok(s),
+            None => Err(YamlPathError::KeyNotFound { path: String::new(), segment: String::from("description") })
+        }
+    }
 
-        match value_r {
-            Ok(v) => match v {
-                Yaml::Real(r) => match r.parse::<f64>() {
-                    Ok(f) => Ok(f),
-                    Err(_) => Err(format!("{}.{} contains invalid float value", path, field))
-                },
-                _ => Err(format!("{}.{} is not a float", path, field))
-            }
-            Err(e) => Err(e)
+    /// complete `ipath` in `docs[doc]`, each candidate starting with `qualifier`
+    fn complete_in(&self, doc: usize, qualifier: &str, ipath: &str, add_descriptions: bool) -> Result<Vec<Completion>, YamlPathError> {
+        match self.ap.parse_partial(ipath) {
+            Ok(partial) => self.complete(&self.docs[doc], &YamlPath::default(), qualifier, &partial, add_descriptions),
+            Err(_) => Ok(Vec::new()) // nothing can complete a malformed path
         }
+    }
+
+    ///
+    /// The candidates for `input`, as [`write_completions`](Descender::write_completions)
+    /// would write them, with descriptions
+    ///
+    /// # Example
+    /// ```rust
+    /// use aep_rust_common::completion::NodeKind;
+    /// use aep_rust_common::yaml_descender::YamlDescender;
+    /// let s = r"---
+    /// GPIO:
+    ///   pins: [{bits: 1}, {bits: 2}]
+    ///   base: {description: the base address, value: 0x1000}
+    /// " ;
+    /// let descender = YamlDescender::new(s, true).unwrap() ;
+    /// let completions = descender.completions("GPIO.") ;
+    /// assert_eq!(completions[0].text, "GPIO.base") ;
+    /// assert_eq!(completions[0].description.as_deref(), Some("the base address")) ;
+    /// assert_eq!(completions[1].kind, NodeKind::Array) ;
+    /// assert!(completions[1].no_space) ;
+    /// ```
+    ///
+    pub fn completions(&self, input: &str) -> Vec<Completion> {
+        self.collect_completions(input, true).unwrap_or_default()
+    }
 
+    /// as [`Descender::write_completions`] for the members of an already
+    /// compiled path
+    pub fn write_path_completions(&self, writer: &mut dyn Write, path: &YamlPath, add_descriptions: bool) -> std::io::Result<()> {
+        let add_descriptions = add_descriptions || self.metadata.get_descriptions ;
+        let partial = PartialPath { path: path.clone(), tail: PartialTail::Key(String::new()) } ;
+        self.render(writer, self.complete(self.document(), &YamlPath::default(), "", &partial, add_descriptions), add_descriptions)
     }
 
-    fn write_completions(&self, writer: &mut dyn Write, ipath: &str, add_descriptions: bool) -> std::io::Result<()>
-    {
-        let mut path = ipath ;
-        let doc = &self.docs[0] ;
-        let re = &self.ap.get_re() ;
-        let mut current_path = String::from("") ;
-        let mut empty_path = true ;
-        let mut match_iter = re.captures_iter(path).peekable();
-        let ap = &self.ap ;
+    pub(crate) fn collect_completions(&self, input: &str, add_descriptions: bool) -> Result<Vec<Completion>, YamlPathError> {
+        if let Some((path, value)) = split_value(input)
+            && let Some(hinted) = self.hinted(path, value, &format!("{}=", path)) {
+            return Ok(hinted);
+        }
+        if !self.across_documents {
+            return self.complete_in(self.document, "", input, add_descriptions);
+        }
+        if let Some((qualifier, rest)) = input.split_once(DOCUMENT_SEPARATOR) {
+            return match self.find_document(qualifier, true) {
+                Some(index) => self.complete_in(self.first_document + index, &format!("{}{}", qualifier, DOCUMENT_SEPARATOR), rest, add_descriptions),
+                None => self.complete_in(self.document, "", input, add_descriptions)
+            } ;
+        }
+        let matches: Vec<String> = (0..self.document_count()).map(|i| self.document_qualifier(i))
+            .filter(|q| q.starts_with(input)).collect() ;
+        if matches.is_empty() {
+            // not a document, a path in the selected one
+            return self.complete_in(self.document, "", input, add_descriptions);
+        }
+        if matches.len() == 1 {
+            let index = self.find_document(&matches[0], true).unwrap_or_default() ;
+            return self.complete_in(self.first_document + index, &format!("{}{}", matches[0], DOCUMENT_SEPARATOR), "", add_descriptions);
+        }
+        Ok(matches.into_iter().map(|q| {
+            let text = format!("{}{}", q, DOCUMENT_SEPARATOR);
+            Completion { display: text.clone(), text, description: None, kind: NodeKind::Hash, terminal: false, no_space: true }
+        }).collect())
+    }
 
-        let mut current = if self.root.as_str().unwrap() == "" { doc } else { &doc.as_hash().unwrap()[&self.root] } ;
+    ///
+    /// write `completions` as the writer based API does: as fish reads them
+    /// in the fish dialect, see [`write_fish`], in the completion protocol
+    /// with descriptions, see [`write_protocol`], and otherwise one per line
+    ///
+    pub(crate) fn render(&self, writer: &mut dyn Write, completions: Result<Vec<Completion>, YamlPathError>, add_descriptions: bool) -> std::io::Result<()> {
+        let completions = completions.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if self.dialect == Dialect::Fish {
+            write_fish(writer, &completions)
+        } else if add_descriptions || self.metadata.get_descriptions {
+            write_protocol(writer, &completions)
+        } else {
+            write_plain(writer, &completions)
+        }
+    }
 
+    fn has_terminal_field(&self, yaml: &Yaml) -> bool {
+        self.metadata.has_terminal_field(yaml)
+    }
+}
 
-        while let Some(captures) = match_iter.next() {
-            let last = match_iter.peek().is_none();
-            let mut key = captures.get(KEY_MATCH).map_or("", |m| m.as_str()) ;
-            let index = captures.get(INDEX_MATCH).map_or(0, |m| m.as_str().parse::<usize>().unwrap()) ;
-            let mut terminated = captures.get(PERIOD_MATCH).is_some() ;
+impl Descender<dyn Write> for YamlDescender {
 
-            loop {
-                match current {
-                    Yaml::Hash(hash) => {
-                        if self.has_terminal_field(current) {
-                            break ;
-                        }
-                        let ykey = Yaml::String(key.to_string());
-                        // let key_vector = hash.iter().map(|(k,v)|k.as_str().unwrap() ).collect::<Vec<&str>>();
-
-                        if terminated {
-                            // no need to search for members starting with key
-                            if !hash.contains_key(&ykey) { return Ok(()); }
-                            current = &hash[&ykey]; // next
-
-                            current_path += ykey.as_str().unwrap();
-                            empty_path = false;
-                            current_path += sep(current, empty_path);
-                            if !last { break; }
-                            path = "";
-                            key = "";
-                            terminated = false;
-                            continue;
-                        }
+    fn set_root(&mut self, path: &str) -> Result<String, YamlPathError> {
+        self.invalidate_paths();
+        Ok(std::mem::replace(&mut self.metadata.root, path.to_string()))
+    }
 
-                        let keys = keys_starting_with(&key, hash, &Default::default());
-                        if keys.is_empty() { return Ok(()); }
-
-                        if keys.len() == 1 {
-                            let ykey = keys[0];
-                            if hash.contains_key(ykey) {
-                                current = &hash[ykey];
-                                current_path += ykey.as_str().unwrap();
-                                empty_path = false;
-                                if !self.has_terminal_field(current) {
-                                    current_path += sep(current, empty_path);
-                                }
-                                if !last { break; }
-                                key = "";
-                                path = "";
-                                continue;
-                            }
-                        }
+    fn get_string_field_or_parent(&self, path: &str, field: &str) -> Result<String, YamlPathError> {
+        match self.field_at(path, field)? {
+            Yaml::String(s) => Ok(s),
+            _ => Err(YamlPathError::TypeMismatch { path: path.to_string(), segment: field.to_string(), expected: "a string" })
+        }
+    }
 
-                        if hash.len() == 1 {
-                            let key = hash.keys().next().unwrap();
-                            current = &hash[key];
-                            current_path += key.as_str().unwrap();
-                            empty_path = false;
-                            current_path += sep(current, empty_path);
-                            continue;
-                        }
+    fn get_int_field_or_parent(&self, path: &str, field: &str) -> Result<i64, YamlPathError> {
+        match self.field_at(path, field)? {
+            Yaml::Integer(i) => Ok(i),
+            _ => Err(YamlPathError::TypeMismatch { path: path.to_string(), segment: field.to_string(), expected: "an integer" })
+        }
+    }
 
-                        let mut has_descriptions = add_descriptions ;
-                        let mut completions:Vec<String> = Vec::new() ;
-                        let mut descriptions:Vec<String> = Vec::new() ;
-
-                        for key in keys.iter().map(|k| k.as_str().unwrap()) { // |k| k.as_str().unwrap()
-                            //writer.write_fmt(format_args!("{}{}\n", current_path, key))?;
-                            completions.push(format_args!("{}{}", current_path, key).to_string());
-                            if has_descriptions {
-                                let description = self.get_description(&hash[&Yaml::String(key.to_string())]);
-                                match description {
-                                    Ok(d) => {
-                                        descriptions.push(d);
-                                    }
-                                    Err(_) => {
-                                        has_descriptions = false;
-                                        continue ;
-                                    }
-                                }
-                            } // has_descriptions
-                        } // for
-                        if has_descriptions {
-                            println!("__descriptions__"); // tag for zsh completion function
-                        }
-                        if has_descriptions {
-                            for (i, c) in completions.iter().enumerate() {
-                                writer.write_fmt(format_args!("{}\n{}\n", c, descriptions[i]))?;
-                            }
-                        }
-                        else {
-                            for c in completions {
-                                writer.write_fmt(format_args!("{}\n", c))?;
-                            }
-                        }
-                        return Ok(());
-                    }
-                    Yaml::Array(array) => {
-                        if captures.get(INDEX_MATCH).is_some() {
-                            if index >= array.len() {
-                                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Index out of bounds"));
-                            }
-                            current = &array[index];
-                            current_path += &ap.apply_index(index) ;
-                            if !self.has_terminal_field(current) {
-                                current_path += sep(current, empty_path);
-                            }
-                            break;
-                        }
-                        if array.len() == 1 {
-                            current_path += &ap.apply_index(0);
-                            empty_path = false;
-                            current = &array[0];
-                            if !self.has_terminal_field(current) {
-                                current_path += sep(current, empty_path);
-                            }
-                            break;
-                        }
-                        for (index, _) in array.iter().enumerate() {
-                            let mut path2 = current_path.to_string();
-
-                            if ap.array_ending(&path2) {
-                                path2.truncate(path.len() - 1);
-                            }
-                            let index_str = &ap.apply_index(index);
-                            writer.write_fmt(format_args!("{}{}\n", path2, index_str))?;
-                        }
-                        return Ok(());
-                    }
-                    _ => { break; }
-                }
-            } // while true
-        } // for captures
-        writer.write_fmt(format_args!("{}\n", current_path))?;
-        Ok(())
+    fn get_bool_field_or_parent(&self, path: &str, field: &str) -> Result<bool, YamlPathError> {
+        match self.field_at(path, field)? {
+            Yaml::Boolean(b) => Ok(b),
+            _ => Err(YamlPathError::TypeMismatch { path: path.to_string(), segment: field.to_string(), expected: "a bool" })
+        }
     }
 
-    fn get_description(&self, yaml: &Yaml) -> Result<String, String> {
-        match yaml {
-            Yaml::Hash(h) => {
-                if h.contains_key(&self.description_key) {
-                    match h[&self.description_key].as_str() {
-                        Some(s) => return Ok(s.to_string()),
-                        None => {return Err("description is not a string".to_string()) ;}
-                    } ;
-                }
-                if h.contains_key(&self.parent_key) {
-                    let parent_path = h[&self.parent_key].as_str().unwrap();
-                    let parent= match self.yaml_descend_path(parent_path) {
-                        Ok(p) => p,
-                        Err(e) => return Err(e)
-                    } ;
-                    return self.get_description(&parent);
-                }
-                return Err("no description found".to_string());
-            }
-            _ => return Err(format!("{:?} is not a hash", yaml))
+    fn get_float_field_or_parent(&self, path: &str, field: &str) -> Result<f64, YamlPathError> {
+        match self.field_at(path, field)? {
+            Yaml::Real(r) => match r.parse::<f64>() {
+                Ok(f) => Ok(f),
+                Err(_) => Err(YamlPathError::TypeMismatch { path: path.to_string(), segment: field.to_string(), expected: "a valid float" })
+            },
+            _ => Err(YamlPathError::TypeMismatch { path: path.to_string(), segment: field.to_string(), expected: "a float" })
         }
+    }
+
+    fn write_completions(&self, writer: &mut dyn Write, ipath: &str, add_descriptions: bool) -> std::io::Result<()>
+    {
+        let add_descriptions = add_descriptions || self.metadata.get_descriptions ;
+        self.render(writer, self.collect_completions(ipath, add_descriptions), add_descriptions)
+    }
 
+    fn get_description(&self, yaml: &Yaml) -> Result<String, YamlPathError> {
+        self.description_in(self.document(), yaml)
     }
 }
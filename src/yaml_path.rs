@@ -22,22 +22,577 @@
 // SOFTWARE.
 // 
 use lazy_static::lazy_static;
-use regex::Regex;
 use yaml_rust::Yaml;
-
-const KEY_MATCH: usize = 1;
-const INDEX_MATCH: usize = 3;
+use yaml_rust::yaml::Hash;
+use crate::arrayparser::{ArrayParser, BashArrayParser};
+use std::collections::HashSet;
+use std::path::Path;
+use crate::error::YamlPathError;
+use crate::include::{mount_dir, split_file_reference, Files, Mounts};
+use crate::merge::{inherited_field, Inheritance};
+use crate::path::{negative_index, slice_range, ParentReference, Segment, ToYamlPath, YamlPath};
 
 lazy_static! {
-    static ref RE: Regex = Regex::new(r"([^.\[\]\\]+)(\.)?|(?:\[(\d+)]?)?").unwrap();
     static ref ParentKey : Yaml = Yaml::String("parent".to_string());
 }
 
+///
+/// Walk `path` from `yaml` and return a reference to the node it addresses.
+/// `ap` is only used to format the path in error messages.
+///
+pub(crate) fn descend<'a>(yaml: &'a Yaml, path: &YamlPath, ap: &dyn ArrayParser) -> Result<&'a Yaml, YamlPathError> {
+    let mut current = yaml;
+    for (i, segment) in path.segments().iter().enumerate() {
+        match segment {
+            Segment::Key(key) => match current {
+                Yaml::Hash(h) => {
+                    let ykey = Yaml::String(key.to_string());
+                    if !h.contains_key(&ykey) {
+                        return Err(YamlPathError::KeyNotFound { path: ap.format_path(path), segment: key.to_string() });
+                    }
+                    current = &h[&ykey];
+                }
+                _ => return Err(YamlPathError::NotAHash { path: ap.format_path(path), segment: key.to_string() }),
+            },
+            Segment::Index(index) => match current {
+                Yaml::Array(a) => {
+                    if *index >= a.len() {
+                        return Err(YamlPathError::IndexOutOfBounds { path: ap.format_path(path), segment: index.to_string() });
+                    }
+                    current = &a[*index];
+                }
+                Yaml::Hash(h) => match numbered_member(h, *index) {
+                    Some(y) => current = y,
+                    None => return Err(YamlPathError::KeyNotFound { path: ap.format_path(path), segment: index.to_string() }),
+                },
+                _ => return Err(YamlPathError::NotAnArray { path: ap.format_path(path), segment: index.to_string() }),
+            },
+            Segment::NegativeIndex(n) => match current {
+                Yaml::Array(a) => match negative_index(*n, a.len()) {
+                    Some(index) => current = &a[index],
+                    None => return Err(YamlPathError::IndexOutOfBounds { path: ap.format_path(path), segment: format!("-{}", n) }),
+                },
+                _ => return Err(YamlPathError::NotAnArray { path: ap.format_path(path), segment: format!("-{}", n) }),
+            },
+            // may match many nodes, see yaml_query
+            _ => return Err(multi_match(path, i, ap)),
+        }
+    }
+    Ok(current)
+}
+
+/// the error for a segment that may match many nodes where one is needed
+fn multi_match(path: &YamlPath, i: usize, ap: &dyn ArrayParser) -> YamlPathError {
+    let segment = &path.segments()[i];
+    let prefix = ap.format_path(&YamlPath::from(path.segments()[..i].to_vec()));
+    YamlPathError::InvalidPath {
+        path: ap.format_path(path),
+        segment: match segment {
+            Segment::Wildcard => String::from("*"),
+            Segment::RecursiveDescent => String::from("**"),
+            _ => segment.selector().unwrap_or_default(),
+        },
+        position: if prefix.is_empty() { 0 } else { prefix.len() + 1 },
+    }
+}
+
+/// an empty node to hold `segment`, an array for an index, otherwise a hash
+fn empty_container(segment: &Segment) -> Yaml {
+    match segment {
+        Segment::Index(_) => Yaml::Array(Vec::new()),
+        _ => Yaml::Hash(Hash::new()),
+    }
+}
+
+///
+/// Apply segment `i` of `path` to `node`.  When `next` is given a missing
+/// member is created to hold it, and a null `node` becomes a hash or an
+/// array, an array being padded with nulls up to the index.
+///
+fn child_mut<'a>(node: &'a mut Yaml, path: &YamlPath, i: usize, next: Option<&Segment>, ap: &dyn ArrayParser) -> Result<&'a mut Yaml, YamlPathError> {
+    let segment = &path.segments()[i];
+    if next.is_some() && node.is_null() {
+        *node = empty_container(segment);
+    }
+    match segment {
+        Segment::Key(key) => match node {
+            Yaml::Hash(h) => {
+                let ykey = Yaml::String(key.to_string());
+                if !h.contains_key(&ykey) {
+                    match next {
+                        Some(next) => { h.insert(ykey.clone(), empty_container(next)); }
+                        None => return Err(YamlPathError::KeyNotFound { path: ap.format_path(path), segment: key.to_string() }),
+                    }
+                }
+                Ok(h.get_mut(&ykey).unwrap())
+            }
+            _ => Err(YamlPathError::NotAHash { path: ap.format_path(path), segment: key.to_string() }),
+        },
+        Segment::Index(index) => match node {
+            Yaml::Array(a) => {
+                if *index >= a.len() {
+                    match next {
+                        Some(next) => {
+                            a.resize(*index, Yaml::Null);
+                            a.push(empty_container(next));
+                        }
+                        None => return Err(YamlPathError::IndexOutOfBounds { path: ap.format_path(path), segment: index.to_string() }),
+                    }
+                }
+                Ok(&mut a[*index])
+            }
+            Yaml::Hash(h) => match numbered_key(h, *index) {
+                Some(k) => Ok(h.get_mut(&k).unwrap()),
+                None => Err(YamlPathError::KeyNotFound { path: ap.format_path(path), segment: index.to_string() }),
+            },
+            _ => Err(YamlPathError::NotAnArray { path: ap.format_path(path), segment: index.to_string() }),
+        },
+        Segment::NegativeIndex(n) => match node {
+            Yaml::Array(a) => match negative_index(*n, a.len()) {
+                Some(index) => Ok(&mut a[index]),
+                None => Err(YamlPathError::IndexOutOfBounds { path: ap.format_path(path), segment: format!("-{}", n) }),
+            },
+            _ => Err(YamlPathError::NotAnArray { path: ap.format_path(path), segment: format!("-{}", n) }),
+        },
+        _ => Err(multi_match(path, i, ap)),
+    }
+}
+
+///
+/// Walk all but the last segment of `path` from `yaml`, creating missing
+/// nodes if asked, and return the node holding the last one.
+///
+fn last_parent_mut<'a>(yaml: &'a mut Yaml, path: &YamlPath, create_missing: bool, ap: &dyn ArrayParser) -> Result<&'a mut Yaml, YamlPathError> {
+    let segments = path.segments();
+    let mut current = yaml;
+    for i in 0..segments.len().saturating_sub(1) {
+        let next = if create_missing { Some(&segments[i + 1]) } else { None };
+        current = child_mut(current, path, i, next, ap)?;
+    }
+    Ok(current)
+}
+
+/// the mutable counterpart of [`descend`]
+pub(crate) fn descend_mut<'a>(yaml: &'a mut Yaml, path: &YamlPath, ap: &dyn ArrayParser) -> Result<&'a mut Yaml, YamlPathError> {
+    let mut current = yaml;
+    for i in 0..path.segments().len() {
+        current = child_mut(current, path, i, None, ap)?;
+    }
+    Ok(current)
+}
+
+///
+/// Set the node at `path` to `value` and return the value it replaced.  A
+/// new member of an existing hash, or an element just past the end of an
+/// existing array, is always added.  With `create_missing` the hashes and
+/// arrays leading to it are created too.
+///
+pub(crate) fn set_in(yaml: &mut Yaml, path: &YamlPath, value: Yaml, create_missing: bool, ap: &dyn ArrayParser) -> Result<Option<Yaml>, YamlPathError> {
+    let Some(last) = path.segments().last() else {
+        return Ok(Some(std::mem::replace(yaml, value)));
+    };
+    let node = last_parent_mut(yaml, path, create_missing, ap)?;
+    if create_missing && node.is_null() {
+        *node = empty_container(last);
+    }
+    match (last, node) {
+        (Segment::Key(key), Yaml::Hash(h)) => Ok(h.insert(Yaml::String(key.to_string()), value)),
+        (Segment::Key(key), _) => Err(YamlPathError::NotAHash { path: ap.format_path(path), segment: key.to_string() }),
+        (Segment::Index(index), Yaml::Array(a)) => {
+            if *index < a.len() {
+                return Ok(Some(std::mem::replace(&mut a[*index], value)));
+            }
+            if *index > a.len() && !create_missing {
+                return Err(YamlPathError::IndexOutOfBounds { path: ap.format_path(path), segment: index.to_string() });
+            }
+            a.resize(*index, Yaml::Null);
+            a.push(value);
+            Ok(None)
+        }
+        (Segment::Index(index), Yaml::Hash(h)) => {
+            let key = numbered_key(h, *index).unwrap_or_else(|| Yaml::String(index.to_string()));
+            Ok(h.insert(key, value))
+        }
+        (Segment::Index(index), _) => Err(YamlPathError::NotAnArray { path: ap.format_path(path), segment: index.to_string() }),
+        (Segment::NegativeIndex(_), node) => {
+            let node = child_mut(node, path, path.segments().len() - 1, None, ap)?;
+            Ok(Some(std::mem::replace(node, value)))
+        }
+        _ => Err(multi_match(path, path.segments().len() - 1, ap)),
+    }
+}
+
+/// Remove the node at `path` and return it, later array elements move down
+pub(crate) fn remove_in(yaml: &mut Yaml, path: &YamlPath, ap: &dyn ArrayParser) -> Result<Yaml, YamlPathError> {
+    let Some(last) = path.segments().last() else {
+        return Err(YamlPathError::InvalidPath { path: String::new(), segment: String::new(), position: 0 });
+    };
+    let i = path.segments().len() - 1;
+    let node = last_parent_mut(yaml, path, false, ap)?;
+    // checks the member exists
+    child_mut(node, path, i, None, ap)?;
+    match (last, node) {
+        (Segment::Key(key), Yaml::Hash(h)) => Ok(h.remove(&Yaml::String(key.to_string())).unwrap()),
+        (Segment::Index(index), Yaml::Array(a)) => Ok(a.remove(*index)),
+        (Segment::Index(index), Yaml::Hash(h)) => Ok(h.remove(&numbered_key(h, *index).unwrap()).unwrap()),
+        (Segment::NegativeIndex(n), Yaml::Array(a)) => Ok(a.remove(negative_index(*n, a.len()).unwrap())),
+        _ => Err(multi_match(path, i, ap)),
+    }
+}
+
+///
+/// The references in a hash's `parent`, which may be a single path or a
+/// list of them
+///
+pub(crate) fn parent_references(h: &Hash) -> Result<Vec<&str>, YamlPathError> {
+    let mismatch = || YamlPathError::TypeMismatch { path: String::new(), segment: String::from("parent"), expected: "a string or a list of strings" };
+    match h.get(&ParentKey) {
+        None => Ok(Vec::new()),
+        Some(Yaml::String(s)) => Ok(vec![s.as_str()]),
+        Some(Yaml::Array(a)) => a.iter().map(|p| p.as_str().ok_or_else(mismatch)).collect(),
+        Some(_) => Err(mismatch()),
+    }
+}
+
+///
+/// What [`Ancestors::search`] looks for in each hash, given the hash and
+/// its path when that is known
+///
+pub(crate) type Test<'a, 't, T> = dyn Fn(&'a Hash, Option<&YamlPath>) -> Result<Option<T>, YamlPathError> + 't;
+
+/// how many `parent` links a lookup follows before giving up
+pub const DEFAULT_MAX_PARENT_DEPTH: usize = 64;
+
+///
+/// Walks `parent` links, the one place references are resolved.
+/// References are paths from `root` in `ap`'s dialect, JSON Pointers,
+/// relative to the node holding them, see [`ParentReference`], or name
+/// another of `files`, `common.yaml#GPIO.words`.  A lookup following more
+/// than `max_depth` links fails.
+///
+pub(crate) struct Ancestors<'a, 'p> {
+    pub root: &'a Yaml,
+    pub ap: &'p dyn ArrayParser,
+    pub max_depth: usize,
+    pub files: Option<&'a Files>,
+    // where the parts of the main tree came from, relative to `root`
+    pub mounts: Mounts,
+}
+
+// a node on a parent chain, its path when known and the file holding it,
+// `None` being the main tree
+type Link<'a> = (&'a Yaml, Option<YamlPath>, Option<&'a Path>);
+
+impl<'a> Ancestors<'a, '_> {
+    ///
+    /// Search `node` and then its ancestors for the first hash where `test`
+    /// finds something.
+    ///
+    /// The order is depth first, left to right: the node itself, then its
+    /// first parent and all of that parent's ancestors, then its second
+    /// parent and its ancestors, and so on.  A single `parent` behaves as a
+    /// list of one.  An ancestor that is not a hash has nothing to inherit.
+    /// A chain leading back to one of its own nodes is a
+    /// [`YamlPathError::ParentCycle`].
+    ///
+    pub fn search<T>(&self, node: &'a Yaml, test: &Test<'a, '_, T>) -> Result<Option<T>, YamlPathError> {
+        self.search_from(test, &mut vec![(node, None, None)], &mut HashSet::new())
+    }
+
+    /// `search` from a node whose path is already known, which may be in
+    /// one of the other files
+    pub fn search_at<T>(&self, node: &'a Yaml, path: YamlPath, test: &Test<'a, '_, T>) -> Result<Option<T>, YamlPathError> {
+        let tree = self.tree_of(node, &path);
+        self.search_from(test, &mut vec![(node, Some(path), tree)], &mut HashSet::new())
+    }
+
+    ///
+    /// The path, node and file `reference` names when held by the node at
+    /// `from` in `tree`.  `from` is looked up, by address, only for a
+    /// relative reference.
+    ///
+    pub fn resolve(&self, reference: &str, node: &'a Yaml, from: &mut Option<YamlPath>,
+                   tree: Option<&'a Path>) -> Result<(YamlPath, &'a Yaml, Option<&'a Path>), YamlPathError> {
+        if let Some(files) = self.files && let Some((name, local)) = split_file_reference(reference) {
+            let dir = self.dir_of(files, node, from, tree);
+            let (file, root) = files.find(dir, name)?;
+            let path = self.ap.parse_reference(local).map_err(|e| e.with_path(reference))?;
+            let parent = descend(root, &path, self.ap).map_err(|e| e.with_path(reference))?;
+            return Ok((path, parent, Some(file)));
+        }
+        let root = self.root_of(tree);
+        let parsed = ParentReference::parse(reference, self.ap)?;
+        if let ParentReference::Relative { .. } = parsed && from.is_none() {
+            *from = yaml_path_of(root, node);
+        }
+        let path = parsed.resolve(from.as_ref()).ok_or_else(|| YamlPathError::InvalidPath {
+            path: reference.to_string(),
+            segment: reference[..reference.len() - reference.trim_start_matches('.').len()].to_string(),
+            position: 0,
+        })?;
+        let parent = descend(root, &path, self.ap)?;
+        Ok((path, parent, tree))
+    }
+
+    // the directory file references held by `node` at `from` in `tree` are
+    // found from, `from` being looked up when the tree mounts other files
+    fn dir_of<'s>(&'s self, files: &'s Files, node: &Yaml, from: &mut Option<YamlPath>, tree: Option<&'a Path>) -> &'s Path {
+        let mounts = match tree {
+            Some(tree) => files.mounts_of(tree),
+            None => Some(&self.mounts),
+        };
+        if let Some(mounts) = mounts && mounts.len() > 1 && from.is_none() {
+            *from = yaml_path_of(self.root_of(tree), node);
+        }
+        mounts.zip(from.as_ref()).and_then(|(mounts, path)| mount_dir(mounts, path))
+            .unwrap_or_else(|| files.dir_of(tree))
+    }
+
+    fn root_of(&self, tree: Option<&Path>) -> &'a Yaml {
+        match self.files {
+            Some(files) => files.root_of(tree, self.root),
+            None => self.root,
+        }
+    }
+
+    // the file holding `node` at `path`, `None` for the main tree
+    fn tree_of(&self, node: &Yaml, path: &YamlPath) -> Option<&'a Path> {
+        let files = self.files?;
+        if descend(self.root, path, self.ap).is_ok_and(|n| std::ptr::eq(n, node)) {
+            return None;
+        }
+        files.tree_holding(node, path)
+    }
+
+    // how a link is named in cycle errors, `file.yaml#path` outside the
+    // main tree
+    fn name(&self, path: &YamlPath, tree: Option<&Path>) -> String {
+        match tree.and_then(Path::file_name) {
+            Some(file) => format!("{}#{}", file.to_string_lossy(), self.ap.format_path(path)),
+            None => self.ap.format_path(path),
+        }
+    }
+
+    // `chain` holds the nodes leading to the last one, the first node's
+    // path is only known if it was given or needed.  `done` holds the nodes
+    // whose ancestors have all been searched without a match, so an
+    // ancestor shared through several parents is searched once.
+    fn search_from<T>(&self, test: &Test<'a, '_, T>, chain: &mut Vec<Link<'a>>,
+                      done: &mut HashSet<*const Yaml>) -> Result<Option<T>, YamlPathError> {
+        let last = chain.len() - 1;
+        let (node, tree) = (chain[last].0, chain[last].2);
+        let Yaml::Hash(h) = node else {
+            return Ok(None);
+        };
+        if done.contains(&(node as *const Yaml)) {
+            return Ok(None);
+        }
+        if let Some(found) = test(h, chain[last].1.as_ref())? {
+            return Ok(Some(found));
+        }
+        for reference in parent_references(h)? {
+            let (path, parent, parent_tree) = self.resolve(reference, node, &mut chain[last].1, tree)?;
+            // a link's path is looked up, by address, only when it is needed
+            let names = |links: &[Link]| {
+                let mut names: Vec<String> = links.iter().map(|(n, p, t)| match p {
+                    Some(p) => self.name(p, *t),
+                    None => self.name(&yaml_path_of(self.root_of(*t), n).unwrap_or_default(), *t),
+                }).collect();
+                names.push(self.name(&path, parent_tree));
+                names.join(" -> ")
+            };
+            if let Some(start) = chain.iter().position(|(n, _, _)| std::ptr::eq(*n, parent)) {
+                return Err(YamlPathError::ParentCycle { path: names(&chain[start..]), segment: reference.to_string() });
+            }
+            if chain.len() > self.max_depth {
+                return Err(YamlPathError::ParentDepthExceeded { path: names(chain), segment: reference.to_string(), limit: self.max_depth });
+            }
+            chain.push((parent, Some(path), parent_tree));
+            let found = self.search_from(test, chain, done);
+            chain.pop();
+            if let Some(found) = found? {
+                return Ok(Some(found));
+            }
+        }
+        done.insert(node);
+        Ok(None)
+    }
+
+    ///
+    /// Every parent cycle and over deep chain below the root, each cycle
+    /// reported once
+    ///
+    pub fn problems(&self) -> Vec<YamlPathError> {
+        let mut seen = Vec::new();
+        let mut problems = Vec::new();
+        // each node starts a search of its own, a chain short enough from
+        // one node may be too long from another that reaches it
+        for (path, node) in all_nodes(self.root) {
+            let e = match self.search_at(node, path, &|_, _| Ok(None::<()>)) {
+                Err(e @ YamlPathError::ParentCycle { .. }) | Err(e @ YamlPathError::ParentDepthExceeded { .. }) => e,
+                _ => continue,
+            };
+            // the same cycle is found from each of its nodes
+            let mut members: Vec<String> = e.path().split(" -> ").map(str::to_string).collect();
+            if let YamlPathError::ParentCycle { .. } = e {
+                members.pop();
+                members.sort();
+            }
+            if !seen.contains(&members) {
+                seen.push(members);
+                problems.push(e);
+            }
+        }
+        problems
+    }
+}
+
+/// resolves a field of a node for filter predicates, following `parent`
+pub(crate) type FieldLookup<'l> = dyn Fn(&Yaml, &str) -> Option<Yaml> + 'l;
+
+///
+/// Collect every node matching `segments` below `node`.  `path` is the
+/// concrete path of `node` and is extended as the match descends.
+///
+pub(crate) fn query_into<'a>(node: &'a Yaml, segments: &[Segment], path: &mut Vec<Segment>,
+                             out: &mut Vec<(YamlPath, &'a Yaml)>, lookup: &FieldLookup) {
+    let Some((first, rest)) = segments.split_first() else {
+        out.push((YamlPath::from(path.clone()), node));
+        return;
+    };
+    match first {
+        Segment::Key(key) => {
+            if let Some(child) = node.as_hash().and_then(|h| h.get(&Yaml::String(key.to_string()))) {
+                path.push(first.clone());
+                query_into(child, rest, path, out, lookup);
+                path.pop();
+            }
+        }
+        Segment::Index(index) => {
+            let child = match node {
+                Yaml::Hash(h) => numbered_member(h, *index),
+                _ => node.as_vec().and_then(|a| a.get(*index)),
+            };
+            if let Some(child) = child {
+                path.push(first.clone());
+                query_into(child, rest, path, out, lookup);
+                path.pop();
+            }
+        }
+        Segment::NegativeIndex(n) => {
+            if let Some(a) = node.as_vec()
+                && let Some(index) = negative_index(*n, a.len()) {
+                path.push(Segment::Index(index));
+                query_into(&a[index], rest, path, out, lookup);
+                path.pop();
+            }
+        }
+        Segment::Slice { start, end } => {
+            if let Some(a) = node.as_vec() {
+                for index in slice_range(*start, *end, a.len()) {
+                    path.push(Segment::Index(index));
+                    query_into(&a[index], rest, path, out, lookup);
+                    path.pop();
+                }
+            }
+        }
+        Segment::Filter(predicate) => {
+            for (segment, child) in children(node) {
+                if predicate.matches(lookup(child, &predicate.field).as_ref()) {
+                    path.push(segment);
+                    query_into(child, rest, path, out, lookup);
+                    path.pop();
+                }
+            }
+        }
+        Segment::Wildcard => {
+            for (segment, child) in children(node) {
+                path.push(segment);
+                query_into(child, rest, path, out, lookup);
+                path.pop();
+            }
+        }
+        Segment::RecursiveDescent => {
+            query_into(node, rest, path, out, lookup);
+            for (segment, child) in children(node) {
+                path.push(segment);
+                query_into(child, segments, path, out, lookup);
+                path.pop();
+            }
+        }
+    }
+}
+
+///
+/// The member of a hash whose key is the number `index`, as a JSON Pointer
+/// token like `/0` names when applied to a hash
+///
+fn numbered_member(h: &Hash, index: usize) -> Option<&Yaml> {
+    numbered_key(h, index).map(|k| &h[&k])
+}
+
+fn numbered_key(h: &Hash, index: usize) -> Option<Yaml> {
+    [Yaml::String(index.to_string()), Yaml::Integer(index as i64)].into_iter().find(|k| h.contains_key(k))
+}
+
+/// the members of a hash with string keys, or the elements of an array
+pub(crate) fn children(node: &Yaml) -> Vec<(Segment, &Yaml)> {
+    match node {
+        Yaml::Hash(h) => h.iter()
+            .filter_map(|(k, v)| k.as_str().map(|k| (Segment::Key(k.to_string()), v)))
+            .collect(),
+        Yaml::Array(a) => a.iter().enumerate().map(|(i, v)| (Segment::Index(i), v)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+///
+/// Find every node matching a path that may contain wildcards or slices.
+/// `*` matches any member of a hash or element of an array, `**` matches
+/// the node itself and everything below it, `[a:b]` matches a range of
+/// elements and `[?field==value]` the members or elements whose field,
+/// possibly inherited through `parent`, satisfies the predicate.  Each match is returned with
+/// its concrete path.  Parts of the path that do not exist simply match
+/// nothing.
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::{Yaml, YamlLoader};
+/// use aep_rust_common::yaml_path::yaml_query;
+/// let s = r"---
+/// pins:
+///   - level: 1
+///   - level: 0
+///     nested:
+///       level: 5
+/// " ;
+///
+/// let yaml = &YamlLoader::load_from_str(s).unwrap()[0] ;
+/// let levels = yaml_query(yaml, "pins[*].level").unwrap() ;
+/// assert_eq!(levels.len(), 2) ;
+/// assert_eq!(levels[1].0.to_string(), "pins[1].level") ;
+///
+/// let all = yaml_query(yaml, "**.level").unwrap() ;
+/// assert_eq!(all.len(), 3) ;
+/// assert_eq!(all[2].1, &Yaml::Integer(5)) ;
+/// ```
+///
+pub fn yaml_query<'a, P: ToYamlPath + ?Sized>(yaml: &'a Yaml, path: &P) -> Result<Vec<(YamlPath, &'a Yaml)>, YamlPathError> {
+    let path = path.to_yaml_path(&BashArrayParser::new())?;
+    let mut out = Vec::new();
+    let lookup = |node: &Yaml, field: &str| yaml_field_parent(yaml, node, field).ok();
+    query_into(yaml, path.segments(), &mut Vec::new(), &mut out, &lookup);
+    Ok(out)
+}
+
 ///
 /// Extract a value from a yaml tree given a 'path'
 /// '.' will separate hash members
 /// '[n]' where n is an index into a list
 ///
+/// `path` may be a string, parsed in the bash dialect, or a compiled [`YamlPath`]
+///
 /// # Example
 ///
 /// ```rust
@@ -61,85 +616,203 @@ lazy_static! {
 ///
 /// ```
 ///
-pub fn yaml_path(yaml: &Yaml, path: &str) -> Result<Yaml, String> {
-    let mut current = yaml;
-    for captures in RE.captures_iter(path) {
-        if captures.get(KEY_MATCH).is_some() {
-            let key = captures.get(KEY_MATCH).unwrap().as_str();
-            match current {
-                Yaml::Hash(h) => {
-                    let ykey = Yaml::String(key.to_string());
-                    if !h.contains_key(&ykey) {
-                        return Err(format!("{} not found in {}", key, path));
-                    }
-                    current = &h[&ykey];
-                }
-                _ => return Err(format!("{} in {} is not a hash", key, path)),
-            } // if str
-        } else if captures.get(INDEX_MATCH).is_some() {
-            let index = captures
-                .get(INDEX_MATCH)
-                .unwrap()
-                .as_str()
-                .parse::<usize>()
-                .unwrap();
-            match current {
-                Yaml::Array(a) => {
-                    if index >= a.len() {
-                        return Err(format!("{} is out of bounds in {}", index, path));
-                    }
-                    current = &a[index];
-                }
-                _ => return Err(format!("{} {} is not an array", path, index)),
-            }
-        }
-        // if index
-        else {
-            return Err(format!("{} is not a valid path", path));
+pub fn yaml_path<P: ToYamlPath + ?Sized>(yaml: &Yaml, path: &P) -> Result<Yaml, YamlPathError> {
+    let ap = BashArrayParser::new();
+    let path = path.to_yaml_path(&ap)?;
+    descend(yaml, &path, &ap).cloned()
+}
+
+///
+/// The path from `root` to `node`, which must be a reference into `root`
+/// itself rather than a copy of one of its nodes.  Nodes are compared by
+/// address, see [`YamlDescender::path_of`](crate::yaml_descender::YamlDescender::path_of)
+/// for an indexed lookup.
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::YamlLoader;
+/// use aep_rust_common::yaml_path::yaml_path_of;
+/// let yaml = &YamlLoader::load_from_str("tree: {array: [1, 2]}").unwrap()[0] ;
+/// let node = &yaml["tree"]["array"][1] ;
+/// assert_eq!(yaml_path_of(yaml, node).unwrap().to_string(), "tree.array[1]") ;
+/// ```
+///
+pub fn yaml_path_of(root: &Yaml, node: &Yaml) -> Option<YamlPath> {
+    let mut path = Vec::new();
+    if find_node(root, node, &mut path) { Some(YamlPath::from(path)) } else { None }
+}
+
+fn find_node(current: &Yaml, node: &Yaml, path: &mut Vec<Segment>) -> bool {
+    if std::ptr::eq(current, node) {
+        return true;
+    }
+    for (segment, child) in children(current) {
+        path.push(segment);
+        if find_node(child, node, path) {
+            return true;
         }
+        path.pop();
     }
-    Ok(current.clone())
+    false
 }
 
-pub fn yaml_path_field(yaml: &Yaml, path: &str, field: &str) -> Result<Yaml, String> {
-    let y = yaml_path(yaml, path);
-    match y {
-        Ok(y) => match y {
-            Yaml::Hash(h) => {
-                let ykey = Yaml::String(field.to_string());
-                if !h.contains_key(&ykey) {
-                    return Err(format!("{} not found in {}", field, path));
-                }
-                Ok(h[&ykey].clone())
-            }
-            _ => Err(format!("{} is not a hash", path)),
-        },
-        Err(e) => Err(e),
-    }
+/// every node below `yaml`, `yaml` itself first, with its path
+pub(crate) fn all_nodes(yaml: &Yaml) -> Vec<(YamlPath, &Yaml)> {
+    let mut out = Vec::new();
+    query_into(yaml, &[Segment::RecursiveDescent], &mut Vec::new(), &mut out, &|_, _| None);
+    out
+}
+
+///
+/// Check every `parent` chain in `yaml` for cycles, and for chains longer
+/// than [`DEFAULT_MAX_PARENT_DEPTH`], resolving references the way
+/// [`yaml_field_parent`] does.  Each problem is reported once.
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::YamlLoader;
+/// use aep_rust_common::yaml_path::parent_cycles;
+/// let s = r"---
+/// a:
+///   parent: b
+/// b:
+///   parent: a
+/// c:
+///   parent: a
+/// " ;
+/// let yaml = &YamlLoader::load_from_str(s).unwrap()[0] ;
+/// let cycles = parent_cycles(yaml) ;
+/// assert_eq!(cycles.len(), 1) ;
+/// assert_eq!(cycles[0].to_string(), "parent cycle a -> b -> a") ;
+/// ```
+///
+pub fn parent_cycles(yaml: &Yaml) -> Vec<YamlPathError> {
+    Ancestors { root: yaml, ap: &BashArrayParser::new(), max_depth: DEFAULT_MAX_PARENT_DEPTH, files: None, mounts: Vec::new() }.problems()
+}
+
+///
+/// A mutable reference to the node at `path`, see [`yaml_path`]
+///
+pub fn yaml_path_mut<'a, P: ToYamlPath + ?Sized>(yaml: &'a mut Yaml, path: &P) -> Result<&'a mut Yaml, YamlPathError> {
+    let ap = BashArrayParser::new();
+    let path = path.to_yaml_path(&ap)?;
+    descend_mut(yaml, &path, &ap)
 }
 
-pub fn yaml_field_parent(root: &Yaml, yaml: &Yaml, field: &str) -> Result<Yaml, String> {
-    match yaml {
+///
+/// Set the node at `path` to `value`, returning the value it replaced.
+///
+/// A new key of an existing hash, or the element just past the end of an
+/// existing array, is added.  With `create_missing` any hashes and arrays
+/// leading to the node are created as well, a key creating a hash and an
+/// index an array padded with nulls.  Otherwise a missing parent is an
+/// error.
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::{Yaml, YamlLoader};
+/// use aep_rust_common::yaml_path::{set_at_path, yaml_path};
+/// let mut yaml = YamlLoader::load_from_str("tree: {}").unwrap().remove(0) ;
+///
+/// set_at_path(&mut yaml, "tree.array[1].field", Yaml::Integer(3), true).unwrap() ;
+/// assert_eq!(yaml_path(&yaml, "tree.array[1].field").unwrap(), Yaml::Integer(3)) ;
+/// assert_eq!(yaml_path(&yaml, "tree.array[0]").unwrap(), Yaml::Null) ;
+///
+/// let old = set_at_path(&mut yaml, "tree.array[1].field", Yaml::Integer(4), false).unwrap() ;
+/// assert_eq!(old, Some(Yaml::Integer(3))) ;
+/// assert!(set_at_path(&mut yaml, "other.field", Yaml::Integer(5), false).is_err()) ;
+/// ```
+///
+pub fn set_at_path<P: ToYamlPath + ?Sized>(yaml: &mut Yaml, path: &P, value: Yaml, create_missing: bool) -> Result<Option<Yaml>, YamlPathError> {
+    let ap = BashArrayParser::new();
+    let path = path.to_yaml_path(&ap)?;
+    set_in(yaml, &path, value, create_missing, &ap)
+}
+
+///
+/// Remove the node at `path` and return it.  Later elements of an array
+/// move down one place.
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::{Yaml, YamlLoader};
+/// use aep_rust_common::yaml_path::{remove_at_path, yaml_path};
+/// let mut yaml = YamlLoader::load_from_str("array: [1, 2, 3]").unwrap().remove(0) ;
+///
+/// assert_eq!(remove_at_path(&mut yaml, "array[0]").unwrap(), Yaml::Integer(1)) ;
+/// assert_eq!(yaml_path(&yaml, "array[0]").unwrap(), Yaml::Integer(2)) ;
+/// assert!(remove_at_path(&mut yaml, "array[2]").is_err()) ;
+/// ```
+///
+pub fn remove_at_path<P: ToYamlPath + ?Sized>(yaml: &mut Yaml, path: &P) -> Result<Yaml, YamlPathError> {
+    let ap = BashArrayParser::new();
+    let path = path.to_yaml_path(&ap)?;
+    remove_in(yaml, &path, &ap)
+}
+
+pub fn yaml_path_field<P: ToYamlPath + ?Sized>(yaml: &Yaml, path: &P, field: &str) -> Result<Yaml, YamlPathError> {
+    let ap = BashArrayParser::new();
+    let path = path.to_yaml_path(&ap)?;
+    match descend(yaml, &path, &ap)? {
         Yaml::Hash(h) => {
             let ykey = Yaml::String(field.to_string());
             if !h.contains_key(&ykey) {
-                if h.contains_key(&ParentKey) {
-                    let parent_path = match &h[&ParentKey] {
-                        Yaml::String(s) => s.to_string(),
-                        _ => return Err(format!("{:?} is not a string", &h[&ParentKey])),
-                    } ;
-
-                    let parent_yaml = match yaml_path(root, &parent_path) {
-                        Ok(y) => y,
-                        Err(e) => return Err(e),
-                    } ;
-                    return yaml_field_parent(root, &parent_yaml, field) ;
-                }
-                return Err(format!("{} not found", field)) ;
+                return Err(YamlPathError::KeyNotFound { path: ap.format_path(&path), segment: field.to_string() });
             }
             Ok(h[&ykey].clone())
         }
-        _ => Err(format!("{:?} is not a hash", yaml)),
+        _ => Err(YamlPathError::NotAHash { path: ap.format_path(&path), segment: field.to_string() }),
+    }
+}
+
+///
+/// Get `field` from the hash `yaml`, or failing that from the nodes named
+/// by its `parent`, which are paths from `root` in the bash dialect or
+/// JSON Pointers.  `parent` may be a list, see [`YamlDescender::get_field_or_parent`]
+/// for the order they are searched in.
+///
+/// [`YamlDescender::get_field_or_parent`]: crate::yaml_descender::YamlDescender::get_field_or_parent
+///
+pub fn yaml_field_parent(root: &Yaml, yaml: &Yaml, field: &str) -> Result<Yaml, YamlPathError> {
+    yaml_field_parent_with(root, yaml, field, Inheritance::FirstFound)
+}
+
+///
+/// [`yaml_field_parent`] choosing how values found on several ancestors
+/// combine, see [`Inheritance`]
+///
+/// # Example
+///
+/// ```rust
+/// use yaml_rust::{Yaml, YamlLoader};
+/// use aep_rust_common::merge::{ArrayMerge, Inheritance};
+/// use aep_rust_common::yaml_path::yaml_field_parent_with;
+/// let s = r"---
+/// base:
+///   timing: {setup: 1, hold: 2}
+/// reg:
+///   parent: base
+///   timing: {hold: 3}
+/// " ;
+/// let yaml = &YamlLoader::load_from_str(s).unwrap()[0] ;
+/// let timing = yaml_field_parent_with(yaml, &yaml["reg"], "timing", Inheritance::DeepMerge(ArrayMerge::Replace)).unwrap() ;
+/// assert_eq!(timing["setup"], Yaml::Integer(1)) ;
+/// assert_eq!(timing["hold"], Yaml::Integer(3)) ;
+/// ```
+///
+pub fn yaml_field_parent_with(root: &Yaml, yaml: &Yaml, field: &str, inheritance: Inheritance) -> Result<Yaml, YamlPathError> {
+    if yaml.as_hash().is_none() {
+        return Err(YamlPathError::NotAHash { path: String::new(), segment: field.to_string() });
+    }
+    let ap = BashArrayParser::new();
+    let ancestors = Ancestors { root, ap: &ap, max_depth: DEFAULT_MAX_PARENT_DEPTH, files: None, mounts: Vec::new() };
+    match inherited_field(&ancestors, yaml, field, inheritance)? {
+        Some(value) => Ok(value),
+        None => Err(YamlPathError::KeyNotFound { path: String::new(), segment: field.to_string() }),
     }
 }
 
@@ -172,8 +845,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, f64) => {{
         match yaml_path($yaml, $path) {
             Ok(y) => match y.as_f64() {
-                Some(v) => Ok(v) as Result<f64, String>,
-                None => Err(format!("{} is not a float", $path)),
+                Some(v) => Ok(v) as Result<f64, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: String::new(), expected: "a float" }),
             },
             Err(e) => Err(e),
         }
@@ -181,8 +854,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, $field:expr, f64) => {{
         match yaml_path_field($yaml, $path, $field) {
             Ok(y) => match y.as_f64() {
-                Some(v) => Ok(v) as Result<f64, String>,
-                None => Err(format!("{} is not a float", $path)),
+                Some(v) => Ok(v) as Result<f64, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: $field.to_string(), expected: "a float" }),
             },
             Err(e) => Err(e),
         }
@@ -190,8 +863,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, i64) => {{
         match yaml_path($yaml, $path) {
             Ok(y) => match y.as_i64() {
-                Some(v) => Ok(v) as Result<i64, String>,
-                None => Err(format!("{} is not an integer", $path)),
+                Some(v) => Ok(v) as Result<i64, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: String::new(), expected: "an integer" }),
             },
             Err(e) => Err(e),
         }
@@ -199,8 +872,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, $field:expr, i64) => {{
         match yaml_path_field($yaml, $path, $field) {
             Ok(y) => match y.as_i64() {
-                Some(v) => Ok(v) as Result<i64, String>,
-                None => Err(format!("{} is not an integer", $path)),
+                Some(v) => Ok(v) as Result<i64, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: $field.to_string(), expected: "an integer" }),
             },
             Err(e) => Err(e),
         }
@@ -208,8 +881,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, bool) => {{
         match yaml_path($yaml, $path) {
             Ok(y) => match y.as_bool() {
-                Some(v) => Ok(v) as Result<bool, String>,
-                None => Err(format!("{} is not a bool", $path)),
+                Some(v) => Ok(v) as Result<bool, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: String::new(), expected: "a bool" }),
             },
             Err(e) => Err(e),
         }
@@ -217,8 +890,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, $field:expr, bool) => {{
         match yaml_path_field($yaml, $path, $field) {
             Ok(y) => match y.as_bool() {
-                Some(v) => Ok(v) as Result<bool, String>,
-                None => Err(format!("{} is not a bool", $path)),
+                Some(v) => Ok(v) as Result<bool, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: $field.to_string(), expected: "a bool" }),
             },
             Err(e) => Err(e),
         }
@@ -226,8 +899,8 @@ macro_rules! yaml_scalar {
     ($yaml:expr, $path:expr, String) => {{
         match yaml_path($yaml, $path) {
             Ok(y) => match y.as_str() {
-                Some(v) => Ok(v.to_string()) as Result<String, String>,
-                None => Err(format!("{} is not a string", $path)),
+                Some(v) => Ok(v.to_string()) as Result<String, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: String::new(), expected: "a string" }),
             },
             Err(e) => Err(e),
         }
@@ -235,8 +908,8 @@ macro_rules! yaml_scalar {
         ($yaml:expr, $path:expr, $field:expr, String) => {{
         match yaml_path_field($yaml, $path, $field) {
             Ok(y) => match y.as_str() {
-                Some(v) => Ok(v.to_string()) as Result<String, String>,
-                None => Err(format!("{} is not a string", $path)),
+                Some(v) => Ok(v.to_string()) as Result<String, $crate::error::YamlPathError>,
+                None => Err($crate::error::YamlPathError::TypeMismatch { path: $path.to_string(), segment: $field.to_string(), expected: "a string" }),
             },
             Err(e) => Err(e),
         }